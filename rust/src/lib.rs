@@ -1,73 +1,1285 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
+use anchor_spl::token::{self, Mint, MintTo, Token, TokenAccount};
 
-declare_id!("11111111111111111111111111111111");
+declare_id!("CnHsuteAwQLYJEafswgoXzwP6AssgokuQnjV1ryU1ikG");
+
+/// Fixed-point scale for `multiply_fixed` factors: 1_000_000 == 1.0
+pub const PRECISION: u64 = 1_000_000;
+
+/// Maximum number of named sub-counters in a `MultiCounter`
+pub const MAX_NAMED_COUNTERS: usize = 16;
+
+/// Maximum byte length of a sub-counter name
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Number of spend categories tracked per counter
+pub const SPEND_CATEGORIES: usize = 8;
+
+/// Number of authorities tracked by the leaderboard
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// Number of leaf indices a claim bitmap can track
+pub const MAX_CLAIMS: usize = 1024;
 
 #[program]
 pub mod counter_program {
     use super::*;
 
-    /// Initialize a new counter account
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        let counter = &mut ctx.accounts.counter;
-        counter.count = 0;
-        counter.authority = ctx.accounts.authority.key();
-        msg!("Counter initialized with value: {}", counter.count);
+    /// Initialize a new counter account
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.init(ctx.accounts.authority.key())?;
+        msg!("Counter initialized with value: {}", counter.count);
+        Ok(())
+    }
+
+    /// Initialize a new counter with four strictly increasing tier thresholds
+    pub fn initialize_tiered(ctx: Context<Initialize>, tiers: [u64; 4]) -> Result<()> {
+        require!(valid_tiers(&tiers), CounterError::InvalidTiers);
+
+        let counter = &mut ctx.accounts.counter;
+        counter.init(ctx.accounts.authority.key())?;
+        counter.tiers = tiers;
+        msg!("Counter initialized with tiers: {:?}", counter.tiers);
+        Ok(())
+    }
+
+    /// Increment the authority's PDA counter, creating it first if needed
+    ///
+    /// Returns `true` when this call created the counter, and emits
+    /// `CounterInitialized` on that path.
+    pub fn increment_or_init(ctx: Context<IncrementOrInit>, amount: u64) -> Result<bool> {
+        let counter = &mut ctx.accounts.counter;
+        let authority = ctx.accounts.authority.key();
+
+        let initialized = counter.authority == Pubkey::default();
+        if initialized {
+            counter.init(authority)?;
+            emit!(CounterInitialized {
+                counter: counter.key(),
+                authority,
+            });
+            msg!("Counter initialized with value: {}", counter.count);
+        } else {
+            require_keys_eq!(counter.authority, authority, CounterError::Unauthorized);
+        }
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)?;
+        Ok(initialized)
+    }
+
+    /// Increment the counter by a specified amount
+    ///
+    /// Pass the `Leaderboard` as the first remaining account to credit the
+    /// authority's cumulative total there as well.
+    pub fn increment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Update<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)?;
+
+        if let Some(leaderboard_info) = ctx.remaining_accounts.first() {
+            // The fixed ["leaderboard"] seeds make the program-owned Leaderboard unique
+            let mut leaderboard = Account::<Leaderboard>::try_from(leaderboard_info)?;
+            leaderboard.record(counter.authority, amount);
+            leaderboard.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Increment the counter and emit an opaque campaign tag for off-chain grouping
+    pub fn increment_tagged(ctx: Context<Update>, amount: u64, tag: [u8; 8]) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)?;
+
+        emit!(IncrementTagged {
+            counter: counter.key(),
+            amount,
+            tag,
+            authority: ctx.accounts.authority.key(),
+        });
+        Ok(())
+    }
+
+    /// Create the program-wide leaderboard of top incrementers
+    pub fn initialize_leaderboard(ctx: Context<InitializeLeaderboard>) -> Result<()> {
+        ctx.accounts.leaderboard.bump = ctx.bumps.leaderboard;
+        msg!("Leaderboard initialized with {} slots", LEADERBOARD_SIZE);
+        Ok(())
+    }
+
+    /// Increment the counter and mint `amount` reward tokens via CPI
+    ///
+    /// The reward mint's authority must be the `["reward", counter]` PDA.
+    pub fn increment_with_reward(ctx: Context<UpdateWithReward>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)?;
+
+        mint_reward(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.reward_account,
+            &ctx.accounts.reward_authority,
+            counter.key(),
+            ctx.bumps.reward_authority,
+            amount,
+        )
+    }
+
+    /// Mint all reward accrued by past operations and zero the pending balance
+    ///
+    /// Uses the same accounts as `increment_with_reward`.
+    pub fn claim_reward(ctx: Context<UpdateWithReward>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        let amount = counter.pending_reward;
+        require!(amount > 0, CounterError::NoPendingReward);
+        counter.pending_reward = 0;
+
+        mint_reward(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.reward_account,
+            &ctx.accounts.reward_authority,
+            counter.key(),
+            ctx.bumps.reward_authority,
+            amount,
+        )
+    }
+
+    /// Set the reward accrued to `pending_reward` by each mutation of the count
+    ///
+    /// Every increment or decrement path earns it (including `sum_into`, claims and
+    /// spends), as do `multiply_fixed`, `apply_decay`, `lock`, `unlock`, `drain` and
+    /// `reset`. `handoff` forfeits whatever is pending instead.
+    pub fn set_reward_per_op(ctx: Context<Update>, reward_per_op: u64) -> Result<()> {
+        ctx.accounts.counter.reward_per_op = reward_per_op;
+        msg!("Reward per operation set to: {}", reward_per_op);
+        Ok(())
+    }
+
+    /// Set (or clear) the SPL mint used for reward CPIs
+    pub fn set_reward_mint(ctx: Context<Update>, reward_mint: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.counter.reward_mint = reward_mint;
+        msg!("Reward mint set to: {:?}", reward_mint);
+        Ok(())
+    }
+
+    /// Increment the counter by a fixed-point amount `whole.frac` at the configured decimals
+    pub fn increment_scaled(ctx: Context<Update>, whole: u64, frac: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        let scale = counter.scale()?;
+        require!(frac < scale, CounterError::InvalidAmount);
+
+        let amount = whole
+            .checked_mul(scale)
+            .and_then(|scaled| scaled.checked_add(frac))
+            .ok_or(CounterError::Overflow)?;
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)
+    }
+
+    /// Increment the counter from a CPI issued directly by `allowed_program`
+    ///
+    /// The caller is identified through the Instructions sysvar: the stack height must
+    /// be exactly one level below the transaction, and the top-level instruction's
+    /// program id must equal `allowed_program`.
+    pub fn increment_via_cpi(ctx: Context<IncrementViaCpi>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        let allowed = counter
+            .allowed_program
+            .ok_or(CounterError::UnauthorizedProgram)?;
+        require!(
+            get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT + 1,
+            CounterError::UnauthorizedProgram
+        );
+        let caller = get_instruction_relative(0, &ctx.accounts.instructions)?;
+        require_keys_eq!(
+            caller.program_id,
+            allowed,
+            CounterError::UnauthorizedProgram
+        );
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        apply_increment(counter, amount)
+    }
+
+    /// Increment the counter at most once per client-supplied key
+    ///
+    /// Each key is recorded permanently in its own `["idempotency", counter, key]`
+    /// PDA, created on first use and paid for by the authority. A repeated key is a
+    /// silent no-op that returns success, so clients can retry freely, even after
+    /// re-signing.
+    pub fn increment_idempotent(
+        ctx: Context<IncrementIdempotent>,
+        amount: u64,
+        key: [u8; 16],
+    ) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        let record = &mut ctx.accounts.idempotency_key;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        if record.counter != Pubkey::default() {
+            msg!("Idempotency key already used, skipping increment");
+            return Ok(());
+        }
+        record.counter = counter.key();
+        record.key = key;
+        record.bump = ctx.bumps.idempotency_key;
+
+        apply_increment(counter, amount)
+    }
+
+    /// Set (or clear) the program allowed to drive `increment_via_cpi`
+    pub fn set_allowed_program(
+        ctx: Context<Update>,
+        allowed_program: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.counter.allowed_program = allowed_program;
+        msg!("Allowed CPI program set to: {:?}", allowed_program);
+        Ok(())
+    }
+
+    /// Set the number of decimals used by scaled increments
+    ///
+    /// Only allowed while the count is zero, since existing raw units would otherwise
+    /// be silently reinterpreted at the new scale.
+    pub fn set_decimals(ctx: Context<Update>, decimals: u8) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        require!(counter.count == 0, CounterError::DecimalsLocked);
+        require!(
+            10u64.checked_pow(decimals as u32).is_some(),
+            CounterError::InvalidDecimals
+        );
+        counter.decimals = decimals;
+        msg!("Counter decimals set to: {}", decimals);
+        Ok(())
+    }
+
+    /// Decrement the counter by a specified amount
+    pub fn decrement(ctx: Context<Update>, amount: u64) -> Result<()> {
+        apply_decrement(&mut ctx.accounts.counter, amount)
+    }
+
+    /// Decrement the counter and attribute the spend to a budget category
+    pub fn spend_categorized(ctx: Context<Update>, amount: u64, category: u8) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        let spent = counter
+            .category_totals
+            .get(category as usize)
+            .copied()
+            .ok_or(CounterError::InvalidCategory)?;
+
+        apply_decrement(counter, amount)?;
+        counter.category_totals[category as usize] =
+            spent.checked_add(amount).ok_or(CounterError::Overflow)?;
+
+        emit!(Spend {
+            counter: counter.key(),
+            amount,
+            category,
+            authority: ctx.accounts.authority.key(),
+        });
+        msg!(
+            "Category {} total: {}",
+            category,
+            counter.category_totals[category as usize]
+        );
+        Ok(())
+    }
+
+    /// Spend `amount` from the counter and mint the same amount of reward tokens
+    ///
+    /// Uses the same accounts as `increment_with_reward`. An over-spend fails with
+    /// `Underflow` before any tokens are minted.
+    pub fn spend(ctx: Context<UpdateWithReward>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        apply_decrement(counter, amount)?;
+
+        mint_reward(
+            &ctx.accounts.token_program,
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.reward_account,
+            &ctx.accounts.reward_authority,
+            counter.key(),
+            ctx.bumps.reward_authority,
+            amount,
+        )
+    }
+
+    /// Multiply the counter by `factor_scaled / PRECISION`, rounding down
+    pub fn multiply_fixed(ctx: Context<Update>, factor_scaled: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(!counter.paused, CounterError::Paused);
+        require!(factor_scaled > 0, CounterError::InvalidAmount);
+
+        let previous_tier = counter.current_tier();
+        counter.accrue()?;
+        let scaled = (counter.count as u128)
+            .checked_mul(factor_scaled as u128)
+            .and_then(|product| product.checked_div(PRECISION as u128))
+            .ok_or(CounterError::Overflow)?;
+        counter.count = u64::try_from(scaled).map_err(|_| CounterError::Overflow)?;
+        counter.circulating = counter
+            .count
+            .checked_sub(counter.locked)
+            .ok_or(CounterError::Underflow)?;
+        counter.earn_reward();
+
+        msg!("Counter scaled to: {}", counter.count);
+        report_growth(counter, previous_tier);
+        Ok(())
+    }
+
+    /// Add the count of the source counter (first remaining account) into this one
+    ///
+    /// Both counters must belong to the signer. With `move_source` the source is
+    /// zeroed, so the total is moved rather than copied; it must then be writable
+    /// and hold no locked value, or the move fails with `InsufficientCirculating`.
+    /// The sum is applied like any other increment, so pausing, `min_increment` and
+    /// tier / capacity events apply to the destination, except that a sum past
+    /// `u64::MAX` always fails with `Overflow`, even in `Wrap` mode. An empty
+    /// source is a no-op and leaves both counters untouched.
+    pub fn sum_into<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SumInto<'info>>,
+        move_source: bool,
+    ) -> Result<()> {
+        let destination = &mut ctx.accounts.destination;
+
+        let source_info = ctx
+            .remaining_accounts
+            .first()
+            .ok_or(CounterError::InvalidSource)?;
+        require_keys_neq!(
+            source_info.key(),
+            destination.key(),
+            CounterError::InvalidSource
+        );
+        let mut source = Account::<Counter>::try_from(source_info)?;
+        require_keys_eq!(
+            source.authority,
+            ctx.accounts.authority.key(),
+            CounterError::Unauthorized
+        );
+
+        if source.count == 0 {
+            msg!(
+                "Source counter {} is empty; nothing to sum",
+                source_info.key
+            );
+            return Ok(());
+        }
+
+        // Locked value must be unlocked on the source before it can be moved;
+        // the destination would otherwise receive it as circulating
+        require!(
+            !move_source || source.locked == 0,
+            CounterError::InsufficientCirculating
+        );
+
+        // Sums never wrap, whatever the destination's overflow mode
+        destination
+            .count
+            .checked_add(source.count)
+            .ok_or(CounterError::Overflow)?;
+        apply_increment(destination, source.count)?;
+        msg!(
+            "Summed {} from {} into counter, now: {}",
+            source.count,
+            source_info.key,
+            destination.count
+        );
+
+        if move_source {
+            require!(source_info.is_writable, CounterError::InvalidSource);
+            require!(!source.paused, CounterError::Paused);
+            source.accrue()?;
+            source.clear();
+            source.exit(&crate::ID)?;
+            msg!("Source counter zeroed");
+        }
+        Ok(())
+    }
+
+    /// Apply linear decay of `decay_per_slot` for every slot since the last decay
+    ///
+    /// Decay only ever lowers the count and never eats into the locked portion.
+    pub fn apply_decay(ctx: Context<Update>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(!counter.paused, CounterError::Paused);
+
+        let slot = Clock::get()?.slot;
+        let elapsed = slot.saturating_sub(counter.last_decay_slot);
+        let decay = counter
+            .decay_per_slot
+            .saturating_mul(elapsed)
+            .min(counter.circulating);
+
+        counter.accrue()?;
+        counter.debit(decay)?;
+        counter.last_decay_slot = slot;
+        counter.earn_reward();
+
+        msg!(
+            "Counter decayed by {} over {} slots to: {}",
+            decay,
+            elapsed,
+            counter.count
+        );
+        Ok(())
+    }
+
+    /// Set the per-slot decay rate; decay accrues from the current slot onward
+    pub fn set_decay_per_slot(ctx: Context<Update>, decay_per_slot: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.decay_per_slot = decay_per_slot;
+        counter.last_decay_slot = Clock::get()?.slot;
+        msg!("Counter decay per slot set to: {}", decay_per_slot);
+        Ok(())
+    }
+
+    /// Reset the counter to zero
+    pub fn reset(ctx: Context<Update>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.accrue()?;
+        counter.clear();
+        counter.earn_reward();
+        msg!("Counter reset to: {}", counter.count);
+        Ok(())
+    }
+
+    /// Log the time-weighted accumulator and slot for off-chain averaging
+    ///
+    /// `average = (acc_now - acc_then) / (slot_now - slot_then)` over any two readings.
+    pub fn describe_twap(ctx: Context<ReadOnly>) -> Result<()> {
+        let counter = &ctx.accounts.counter;
+        let slot = Clock::get()?.slot;
+        msg!(
+            "TWAP: {{ accumulator: {}, slot: {} }}",
+            counter.accumulator_at(slot),
+            slot
+        );
+        Ok(())
+    }
+
+    /// Reset the counter and hand it to `new_authority` in one instruction
+    ///
+    /// The outgoing owner's history is cleared: the count (locked and circulating),
+    /// `category_totals` and the `merkle_root`, so no claim against it can credit the
+    /// new owner. `allowed_program` is cleared too, so no program the outgoing owner
+    /// picked can keep driving `increment_via_cpi`. Any unclaimed `pending_reward` is
+    /// forfeited, so the outgoing authority should call `claim_reward` first. Other
+    /// settings (tiers, decimals, modes, margins, decay, reward configuration, pause)
+    /// and the TWAP accumulator carry over.
+    pub fn handoff(ctx: Context<Update>, new_authority: Pubkey) -> Result<()> {
+        require_keys_neq!(
+            new_authority,
+            Pubkey::default(),
+            CounterError::InvalidAuthority
+        );
+
+        let counter = &mut ctx.accounts.counter;
+        let previous_count = counter.count;
+        let old_authority = counter.authority;
+
+        counter.accrue()?;
+        counter.clear();
+        // Unclaimed rewards, spend totals, airdrops and CPI access belong to the
+        // outgoing owner; never pass them on
+        counter.pending_reward = 0;
+        counter.category_totals = [0; SPEND_CATEGORIES];
+        counter.merkle_root = [0; 32];
+        counter.allowed_program = None;
+        counter.authority = new_authority;
+
+        emit!(CounterReset {
+            counter: counter.key(),
+            previous_count,
+        });
+        emit!(AuthorityChanged {
+            counter: counter.key(),
+            old_authority,
+            new_authority,
+        });
+        msg!(
+            "Counter reset and handed off: {} -> {}",
+            old_authority,
+            new_authority
+        );
+        Ok(())
+    }
+
+    /// Last-resort reassignment of a counter whose authority key was lost
+    ///
+    /// Only the program's upgrade authority can sign this. It lets whoever can
+    /// upgrade the program take over any counter, so deployments that want
+    /// counters to be fully self-custodial should make the program immutable.
+    /// The count and any `pending_reward` are kept, so the recovered authority can
+    /// still claim rewards earned under the lost key.
+    pub fn recover_authority(ctx: Context<Recover>, new_authority: Pubkey) -> Result<()> {
+        require_keys_neq!(
+            new_authority,
+            Pubkey::default(),
+            CounterError::InvalidAuthority
+        );
+
+        let counter = &mut ctx.accounts.counter;
+        let old_authority = counter.authority;
+        counter.authority = new_authority;
+
+        emit!(AuthorityChanged {
+            counter: counter.key(),
+            old_authority,
+            new_authority,
+        });
+        msg!(
+            "Counter authority recovered: {} -> {}",
+            old_authority,
+            new_authority
+        );
+        Ok(())
+    }
+
+    /// Emit a `CounterConfig` snapshot of every configurable field
+    pub fn describe_config(ctx: Context<ReadOnly>) -> Result<()> {
+        let counter = &ctx.accounts.counter;
+        let config = CounterConfig {
+            counter: counter.key(),
+            authority: counter.authority,
+            paused: counter.paused,
+            overflow_mode: counter.overflow_mode,
+            warn_margin: counter.warn_margin,
+            min_increment: counter.min_increment,
+            decay_per_slot: counter.decay_per_slot,
+            decimals: counter.decimals,
+            tiers: counter.tiers,
+            allowed_program: counter.allowed_program,
+            reward_mint: counter.reward_mint,
+            reward_per_op: counter.reward_per_op,
+            merkle_root: counter.merkle_root,
+        };
+        msg!("Config: {:?}", config);
+        emit!(config);
+        Ok(())
+    }
+
+    /// Log the state of every counter passed in `remaining_accounts`
+    pub fn describe_many<'info>(ctx: Context<'_, '_, 'info, 'info, ReadMany>) -> Result<()> {
+        for account in ctx.remaining_accounts.iter() {
+            match Account::<Counter>::try_from(account) {
+                Ok(counter) => msg!(
+                    "{{ pubkey: {}, count: {}, authority: {} }}",
+                    account.key,
+                    counter.count,
+                    counter.authority
+                ),
+                Err(_) => msg!("Skipping {}: not a counter account", account.key),
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose whether increments past `u64::MAX` error or wrap around
+    pub fn set_overflow_mode(ctx: Context<Update>, overflow_mode: OverflowMode) -> Result<()> {
+        ctx.accounts.counter.overflow_mode = overflow_mode;
+        msg!("Counter overflow mode set to: {:?}", overflow_mode);
+        Ok(())
+    }
+
+    /// Zero the counter and emit the drained amount, unlike a plain reset
+    pub fn drain(ctx: Context<Update>) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        let amount = counter.count;
+        counter.accrue()?;
+        counter.clear();
+        counter.earn_reward();
+
+        emit!(Drained {
+            counter: counter.key(),
+            amount,
+        });
+        msg!("Counter drained by: {}", amount);
+        Ok(())
+    }
+
+    /// Set the smallest amount any single increment may add
+    pub fn set_min_increment(ctx: Context<Update>, min_increment: u64) -> Result<()> {
+        require!(min_increment > 0, CounterError::InvalidAmount);
+        ctx.accounts.counter.min_increment = min_increment;
+        msg!("Counter minimum increment set to: {}", min_increment);
+        Ok(())
+    }
+
+    /// Set how close to `u64::MAX` increments start emitting `NearCapacity` (0 disables)
+    pub fn set_warn_margin(ctx: Context<Update>, warn_margin: u64) -> Result<()> {
+        ctx.accounts.counter.warn_margin = warn_margin;
+        msg!("Counter warn margin set to: {}", warn_margin);
+        Ok(())
+    }
+
+    /// Pause or unpause increments and decrements; reset stays available
+    pub fn set_paused(ctx: Context<Update>, paused: bool) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.paused = paused;
+
+        emit!(PausedChanged {
+            counter: counter.key(),
+            paused,
+        });
+        msg!("Counter paused: {}", paused);
+        Ok(())
+    }
+
+    /// Set the Merkle root committing to claimable increments
+    ///
+    /// Claim bitmaps are keyed by root, so rotating to a new root starts a fresh
+    /// distribution whose indices are all unclaimed; call `initialize_claims` again.
+    pub fn set_merkle_root(ctx: Context<Update>, merkle_root: [u8; 32]) -> Result<()> {
+        ctx.accounts.counter.merkle_root = merkle_root;
+        msg!("Merkle root updated");
+        Ok(())
+    }
+
+    /// Create the bitmap that records which claim indices of the current root were used
+    pub fn initialize_claims(ctx: Context<InitializeClaims>) -> Result<()> {
+        let claims = &mut ctx.accounts.claims;
+        claims.counter = ctx.accounts.counter.key();
+        claims.merkle_root = ctx.accounts.counter.merkle_root;
+        claims.bump = ctx.bumps.claims;
+        msg!("Claim bitmap initialized for {} leaves", MAX_CLAIMS);
+        Ok(())
+    }
+
+    /// Increment the counter by a pre-committed amount proven against the Merkle root
+    ///
+    /// The amount was fixed when the root was committed, so `min_increment` does not
+    /// apply: raising it later cannot strand a leaf. Pausing still blocks claims.
+    pub fn claim_increment(
+        ctx: Context<ClaimIncrement>,
+        index: u32,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        let claims = &mut ctx.accounts.claims;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+        require!((index as usize) < MAX_CLAIMS, CounterError::InvalidProof);
+        require!(!claims.is_claimed(index), CounterError::AlreadyClaimed);
+
+        let leaf = claim_leaf(index, &ctx.accounts.claimant.key(), amount);
+        require!(
+            verify_proof(&proof, counter.merkle_root, leaf),
+            CounterError::InvalidProof
+        );
+
+        require!(!counter.paused, CounterError::Paused);
+        claims.set_claimed(index);
+        msg!("Claim {} redeemed for {}", index, amount);
+
+        credit_increment(counter, amount)
+    }
+
+    /// Move part of the circulating count into the locked portion
+    pub fn lock(ctx: Context<Update>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        counter.circulating = counter
+            .circulating
+            .checked_sub(amount)
+            .ok_or(CounterError::InsufficientCirculating)?;
+        counter.locked = counter
+            .locked
+            .checked_add(amount)
+            .ok_or(CounterError::Overflow)?;
+        counter.earn_reward();
+
+        msg!("Locked {}, total locked: {}", amount, counter.locked);
+        Ok(())
+    }
+
+    /// Release part of the locked portion back into circulation
+    pub fn unlock(ctx: Context<Update>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        counter.locked = counter
+            .locked
+            .checked_sub(amount)
+            .ok_or(CounterError::Underflow)?;
+        counter.circulating = counter
+            .circulating
+            .checked_add(amount)
+            .ok_or(CounterError::Overflow)?;
+        counter.earn_reward();
+
+        msg!("Unlocked {}, total locked: {}", amount, counter.locked);
+        Ok(())
+    }
+
+    /// Log the locked / circulating breakdown of the count
+    pub fn describe_supply(ctx: Context<ReadOnly>) -> Result<()> {
+        let counter = &ctx.accounts.counter;
+        msg!(
+            "Supply: {{ count: {}, locked: {}, circulating: {} }}",
+            counter.count,
+            counter.locked,
+            counter.circulating
+        );
+        Ok(())
+    }
+
+    /// Initialize an account holding up to `MAX_NAMED_COUNTERS` named sub-counters
+    pub fn initialize_multi(ctx: Context<InitializeMulti>) -> Result<()> {
+        let multi = &mut ctx.accounts.multi_counter;
+        multi.authority = ctx.accounts.authority.key();
+        multi.counters = Vec::new();
+        msg!("Multi-counter initialized");
+        Ok(())
+    }
+
+    /// Add a new sub-counter starting at zero
+    pub fn add_named(ctx: Context<UpdateMulti>, name: String) -> Result<()> {
+        let multi = &mut ctx.accounts.multi_counter;
+
+        require!(
+            !name.is_empty() && name.len() <= MAX_NAME_LEN,
+            CounterError::InvalidName
+        );
+        require!(multi.find(&name).is_none(), CounterError::DuplicateName);
+        require!(
+            multi.counters.len() < MAX_NAMED_COUNTERS,
+            CounterError::TooManyCounters
+        );
+
+        msg!("Added named counter: {}", name);
+        multi.counters.push(NamedCounter { name, value: 0 });
+        Ok(())
+    }
+
+    /// Increment a named sub-counter
+    pub fn increment_named(ctx: Context<UpdateMulti>, name: String, amount: u64) -> Result<()> {
+        let multi = &mut ctx.accounts.multi_counter;
+
+        require!(amount > 0, CounterError::InvalidAmount);
+
+        let entry = multi.find_mut(&name).ok_or(CounterError::CounterNotFound)?;
+        entry.value = entry
+            .value
+            .checked_add(amount)
+            .ok_or(CounterError::Overflow)?;
+
+        msg!("Counter {} incremented to: {}", name, entry.value);
+        Ok(())
+    }
+
+    /// Read a named sub-counter, returned as instruction return data
+    pub fn get_named(ctx: Context<ReadMulti>, name: String) -> Result<u64> {
+        let entry = ctx
+            .accounts
+            .multi_counter
+            .find(&name)
+            .ok_or(CounterError::CounterNotFound)?;
+        msg!("Counter {} value: {}", name, entry.value);
+        Ok(entry.value)
+    }
+
+    /// Log every named sub-counter and emit them in a `NamedCountersListed` event
+    pub fn list_named(ctx: Context<ReadMulti>) -> Result<()> {
+        let multi = &ctx.accounts.multi_counter;
+        for entry in multi.counters.iter() {
+            msg!("{}: {}", entry.name, entry.value);
+        }
+        emit!(NamedCountersListed {
+            multi_counter: multi.key(),
+            counters: multi.counters.clone(),
+        });
+        Ok(())
+    }
+}
+
+/// Shared increment path: adds `amount` and reports tier and capacity progress
+fn apply_increment(counter: &mut Account<Counter>, amount: u64) -> Result<()> {
+    require!(!counter.paused, CounterError::Paused);
+    require!(amount >= counter.min_increment, CounterError::BelowMinimum);
+
+    credit_increment(counter, amount)
+}
+
+/// `apply_increment` past its pause and `min_increment` checks
+fn credit_increment(counter: &mut Account<Counter>, amount: u64) -> Result<()> {
+    let previous_tier = counter.current_tier();
+
+    counter.accrue()?;
+    match counter.overflow_mode {
+        OverflowMode::Error => counter.credit(amount)?,
+        OverflowMode::Wrap => {
+            if counter.count.checked_add(amount).is_some() {
+                counter.credit(amount)?;
+            } else {
+                counter.wrap(amount);
+                msg!("Counter wrapped past u64::MAX");
+            }
+        }
+    }
+    counter.earn_reward();
+    msg!("Counter incremented to: {}", counter.count);
+
+    report_growth(counter, previous_tier);
+    Ok(())
+}
+
+/// Emit `TierChanged` / `NearCapacity` after the count grew from `previous_tier`
+fn report_growth(counter: &Account<Counter>, previous_tier: u8) {
+    if counter.is_tiered() {
+        let tier = counter.current_tier();
+        msg!("Counter tier: {}", tier);
+        if tier > previous_tier {
+            emit!(TierChanged {
+                counter: counter.key(),
+                previous_tier,
+                tier,
+            });
+        }
+    }
+
+    let remaining = u64::MAX - counter.count;
+    if counter.warn_margin > 0 && remaining <= counter.warn_margin {
+        emit!(NearCapacity {
+            counter: counter.key(),
+            remaining,
+        });
+        msg!("Counter near capacity, remaining: {}", remaining);
+    }
+}
+
+/// Shared decrement path: removes `amount` from the circulating count
+fn apply_decrement(counter: &mut Account<Counter>, amount: u64) -> Result<()> {
+    require!(!counter.paused, CounterError::Paused);
+    require!(amount > 0, CounterError::InvalidAmount);
+
+    counter.accrue()?;
+    counter.debit(amount)?;
+    counter.earn_reward();
+
+    msg!("Counter decremented to: {}", counter.count);
+    Ok(())
+}
+
+/// Mint reward tokens signed by the counter's `["reward", counter]` PDA
+fn mint_reward<'info>(
+    token_program: &Program<'info, Token>,
+    mint: &Account<'info, Mint>,
+    to: &Account<'info, TokenAccount>,
+    authority: &UncheckedAccount<'info>,
+    counter: Pubkey,
+    bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[b"reward", counter.as_ref(), &[bump]]];
+    token::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            MintTo {
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+    )?;
+    msg!("Minted {} reward tokens", amount);
+    Ok(())
+}
+
+/// Tier thresholds must be non-zero and strictly increasing
+fn valid_tiers(tiers: &[u64; 4]) -> bool {
+    tiers[0] > 0 && tiers.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+/// Merkle leaf committing `amount` to `claimant` at `index`
+///
+/// The leaf binds the signing claimant rather than the counter's authority: every
+/// leaf of a counter would share the same authority, so anyone holding a proof
+/// could redeem it. Binding the claimant means only the intended user can claim.
+pub fn claim_leaf(index: u32, claimant: &Pubkey, amount: u64) -> [u8; 32] {
+    keccak::hashv(&[
+        &index.to_le_bytes(),
+        claimant.as_ref(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Verify a Merkle proof using sorted-pair keccak hashing
+fn verify_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            keccak::hashv(&[&node, sibling]).to_bytes()
+        } else {
+            keccak::hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+    computed == root
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Counter::INIT_SPACE
+    )]
+    pub counter: Account<'info, Counter>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IncrementOrInit<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + Counter::INIT_SPACE,
+        seeds = [b"counter", authority.key().as_ref()],
+        bump
+    )]
+    pub counter: Account<'info, Counter>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Update<'info> {
+    #[account(
+        mut,
+        has_one = authority @ CounterError::Unauthorized
+    )]
+    pub counter: Account<'info, Counter>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLeaderboard<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Leaderboard::INIT_SPACE,
+        seeds = [b"leaderboard"],
+        bump
+    )]
+    pub leaderboard: Account<'info, Leaderboard>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SumInto<'info> {
+    #[account(
+        mut,
+        has_one = authority @ CounterError::Unauthorized
+    )]
+    pub destination: Account<'info, Counter>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateWithReward<'info> {
+    #[account(
+        mut,
+        has_one = authority @ CounterError::Unauthorized,
+        constraint = counter.reward_mint == Some(reward_mint.key()) @ CounterError::InvalidRewardMint
+    )]
+    pub counter: Account<'info, Counter>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub reward_mint: Account<'info, Mint>,
+
+    #[account(mut, token::mint = reward_mint)]
+    pub reward_account: Account<'info, TokenAccount>,
+
+    /// CHECK: PDA used only as the reward mint authority
+    #[account(seeds = [b"reward", counter.key().as_ref()], bump)]
+    pub reward_authority: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct IncrementViaCpi<'info> {
+    #[account(mut)]
+    pub counter: Account<'info, Counter>,
+
+    /// CHECK: address is constrained to the Instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, key: [u8; 16])]
+pub struct IncrementIdempotent<'info> {
+    #[account(
+        mut,
+        has_one = authority @ CounterError::Unauthorized
+    )]
+    pub counter: Account<'info, Counter>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + IdempotencyKey::INIT_SPACE,
+        seeds = [b"idempotency", counter.key().as_ref(), key.as_ref()],
+        bump
+    )]
+    pub idempotency_key: Account<'info, IdempotencyKey>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Recover<'info> {
+    #[account(mut)]
+    pub counter: Account<'info, Counter>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()) @ CounterError::Unauthorized
+    )]
+    pub program: Program<'info, crate::program::CounterProgram>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(upgrade_authority.key()) @ CounterError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub upgrade_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReadOnly<'info> {
+    pub counter: Account<'info, Counter>,
+}
+
+#[derive(Accounts)]
+pub struct ReadMany {}
+
+#[derive(Accounts)]
+pub struct InitializeClaims<'info> {
+    #[account(
+        has_one = authority @ CounterError::Unauthorized
+    )]
+    pub counter: Account<'info, Counter>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ClaimBitmap::INIT_SPACE,
+        seeds = [b"claims", counter.key().as_ref(), counter.merkle_root.as_ref()],
+        bump
+    )]
+    pub claims: Account<'info, ClaimBitmap>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimIncrement<'info> {
+    #[account(mut)]
+    pub counter: Account<'info, Counter>,
+
+    #[account(
+        mut,
+        seeds = [b"claims", counter.key().as_ref(), counter.merkle_root.as_ref()],
+        bump = claims.bump
+    )]
+    pub claims: Account<'info, ClaimBitmap>,
+
+    pub claimant: Signer<'info>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Counter {
+    pub count: u64,
+    pub authority: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub locked: u64,
+    pub circulating: u64,
+    pub decimals: u8,
+    pub tiers: [u64; 4],
+    pub allowed_program: Option<Pubkey>,
+    pub reward_mint: Option<Pubkey>,
+    pub reward_per_op: u64,
+    pub pending_reward: u64,
+    pub paused: bool,
+    pub overflow_mode: OverflowMode,
+    pub warn_margin: u64,
+    pub min_increment: u64,
+    pub category_totals: [u64; SPEND_CATEGORIES],
+    pub decay_per_slot: u64,
+    pub last_decay_slot: u64,
+    pub accumulator: u128,
+    pub last_slot: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum OverflowMode {
+    /// Reject increments that would exceed `u64::MAX`
+    Error,
+    /// Wrap around modulo 2^64, like a ring counter
+    Wrap,
+}
+
+impl Counter {
+    /// Set up a freshly created counter owned by `authority`
+    pub fn init(&mut self, authority: Pubkey) -> Result<()> {
+        self.count = 0;
+        self.authority = authority;
+        self.min_increment = 1;
+        self.last_slot = Clock::get()?.slot;
+        self.last_decay_slot = self.last_slot;
         Ok(())
     }
 
-    /// Increment the counter by a specified amount
-    pub fn increment(ctx: Context<Update>, amount: u64) -> Result<()> {
-        let counter = &mut ctx.accounts.counter;
+    /// Fold `count * elapsed_slots` into the accumulator; call before changing `count`
+    ///
+    /// The accumulator wraps on overflow; clients should take differences between
+    /// readings with wrapping subtraction.
+    pub fn accrue(&mut self) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        self.accumulator = self.accumulator_at(slot);
+        self.last_slot = slot;
+        Ok(())
+    }
 
-        require!(
-            amount > 0,
-            CounterError::InvalidAmount
-        );
+    /// Accumulator value as of `slot`, without mutating state
+    pub fn accumulator_at(&self, slot: u64) -> u128 {
+        let elapsed = slot.saturating_sub(self.last_slot) as u128;
+        self.accumulator
+            .wrapping_add((self.count as u128).wrapping_mul(elapsed))
+    }
 
-        counter.count = counter
+    /// Zero the count along with its locked / circulating split
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.locked = 0;
+        self.circulating = 0;
+    }
+
+    /// Add to the count; new value enters the circulating portion
+    pub fn credit(&mut self, amount: u64) -> Result<()> {
+        self.count = self
             .count
             .checked_add(amount)
             .ok_or(CounterError::Overflow)?;
-
-        msg!("Counter incremented to: {}", counter.count);
+        self.circulating = self
+            .circulating
+            .checked_add(amount)
+            .ok_or(CounterError::Overflow)?;
         Ok(())
     }
 
-    /// Decrement the counter by a specified amount
-    pub fn decrement(ctx: Context<Update>, amount: u64) -> Result<()> {
-        let counter = &mut ctx.accounts.counter;
+    /// Whether tier thresholds were configured at initialization
+    pub fn is_tiered(&self) -> bool {
+        self.tiers[0] > 0
+    }
 
-        require!(
-            amount > 0,
-            CounterError::InvalidAmount
-        );
+    /// Number of tier thresholds the count has reached (0 to 4)
+    pub fn current_tier(&self) -> u8 {
+        if !self.is_tiered() {
+            return 0;
+        }
+        self.tiers.iter().filter(|&&t| self.count >= t).count() as u8
+    }
 
-        counter.count = counter
-            .count
-            .checked_sub(amount)
-            .ok_or(CounterError::Underflow)?;
+    /// Raw units per whole unit, i.e. `10^decimals`
+    pub fn scale(&self) -> Result<u64> {
+        10u64
+            .checked_pow(self.decimals as u32)
+            .ok_or_else(|| error!(CounterError::InvalidDecimals))
+    }
 
-        msg!("Counter decremented to: {}", counter.count);
-        Ok(())
+    /// Add to the count modulo 2^64; any locked portion is released by the wrap
+    pub fn wrap(&mut self, amount: u64) {
+        self.count = self.count.wrapping_add(amount);
+        self.locked = 0;
+        self.circulating = self.count;
     }
 
-    /// Reset the counter to zero
-    pub fn reset(ctx: Context<Update>) -> Result<()> {
-        let counter = &mut ctx.accounts.counter;
-        counter.count = 0;
-        msg!("Counter reset to: {}", counter.count);
+    /// Credit `reward_per_op` to `pending_reward` for one mutating operation
+    pub fn earn_reward(&mut self) {
+        self.pending_reward = self.pending_reward.saturating_add(self.reward_per_op);
+    }
+
+    /// Remove from the count; locked value cannot be decremented away
+    pub fn debit(&mut self, amount: u64) -> Result<()> {
+        let count = self
+            .count
+            .checked_sub(amount)
+            .ok_or(CounterError::Underflow)?;
+        self.circulating = self
+            .circulating
+            .checked_sub(amount)
+            .ok_or(CounterError::InsufficientCirculating)?;
+        self.count = count;
         Ok(())
     }
 }
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct InitializeMulti<'info> {
     #[account(
         init,
         payer = authority,
-        space = 8 + Counter::INIT_SPACE
+        space = 8 + MultiCounter::INIT_SPACE
     )]
-    pub counter: Account<'info, Counter>,
+    pub multi_counter: Account<'info, MultiCounter>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
@@ -76,21 +1288,213 @@ pub struct Initialize<'info> {
 }
 
 #[derive(Accounts)]
-pub struct Update<'info> {
+pub struct UpdateMulti<'info> {
     #[account(
         mut,
         has_one = authority @ CounterError::Unauthorized
     )]
-    pub counter: Account<'info, Counter>,
+    pub multi_counter: Account<'info, MultiCounter>,
 
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ReadMulti<'info> {
+    pub multi_counter: Account<'info, MultiCounter>,
+}
+
 #[account]
 #[derive(InitSpace)]
-pub struct Counter {
-    pub count: u64,
+pub struct Leaderboard {
+    pub bump: u8,
+    pub len: u8,
+    /// Sorted by `total`, highest first; only the first `len` entries are used
+    pub entries: [LeaderboardEntry; LEADERBOARD_SIZE],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct LeaderboardEntry {
+    pub authority: Pubkey,
+    pub total: u64,
+}
+
+impl Leaderboard {
+    /// Credit `amount` to `authority`, inserting it if it qualifies for the top N
+    ///
+    /// Totals only accumulate while an authority is on the board; one that drops off
+    /// re-enters with just its next increment.
+    pub fn record(&mut self, authority: Pubkey, amount: u64) {
+        let len = self.len as usize;
+        let mut index = match self.entries[..len]
+            .iter()
+            .position(|entry| entry.authority == authority)
+        {
+            Some(index) => {
+                self.entries[index].total = self.entries[index].total.saturating_add(amount);
+                index
+            }
+            None if len < LEADERBOARD_SIZE => {
+                self.len += 1;
+                self.entries[len] = LeaderboardEntry {
+                    authority,
+                    total: amount,
+                };
+                len
+            }
+            None if amount > self.entries[LEADERBOARD_SIZE - 1].total => {
+                self.entries[LEADERBOARD_SIZE - 1] = LeaderboardEntry {
+                    authority,
+                    total: amount,
+                };
+                LEADERBOARD_SIZE - 1
+            }
+            None => return,
+        };
+
+        while index > 0 && self.entries[index].total > self.entries[index - 1].total {
+            self.entries.swap(index, index - 1);
+            index -= 1;
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MultiCounter {
+    pub authority: Pubkey,
+    #[max_len(MAX_NAMED_COUNTERS)]
+    pub counters: Vec<NamedCounter>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct NamedCounter {
+    #[max_len(MAX_NAME_LEN)]
+    pub name: String,
+    pub value: u64,
+}
+
+impl MultiCounter {
+    pub fn find(&self, name: &str) -> Option<&NamedCounter> {
+        self.counters.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut NamedCounter> {
+        self.counters.iter_mut().find(|entry| entry.name == name)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimBitmap {
+    pub counter: Pubkey,
+    /// Root this bitmap tracks; each distribution gets its own bitmap
+    pub merkle_root: [u8; 32],
+    pub bump: u8,
+    pub claimed: [u8; MAX_CLAIMS / 8],
+}
+
+impl ClaimBitmap {
+    pub fn is_claimed(&self, index: u32) -> bool {
+        let index = index as usize;
+        self.claimed[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    pub fn set_claimed(&mut self, index: u32) {
+        let index = index as usize;
+        self.claimed[index / 8] |= 1 << (index % 8);
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct IdempotencyKey {
+    pub counter: Pubkey,
+    pub key: [u8; 16],
+    pub bump: u8,
+}
+
+#[event]
+pub struct CounterInitialized {
+    pub counter: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct NamedCountersListed {
+    pub multi_counter: Pubkey,
+    pub counters: Vec<NamedCounter>,
+}
+
+#[event]
+pub struct IncrementTagged {
+    pub counter: Pubkey,
+    pub amount: u64,
+    pub tag: [u8; 8],
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct TierChanged {
+    pub counter: Pubkey,
+    pub previous_tier: u8,
+    pub tier: u8,
+}
+
+#[event]
+pub struct CounterReset {
+    pub counter: Pubkey,
+    pub previous_count: u64,
+}
+
+#[event]
+pub struct AuthorityChanged {
+    pub counter: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct Drained {
+    pub counter: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct NearCapacity {
+    pub counter: Pubkey,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct Spend {
+    pub counter: Pubkey,
+    pub amount: u64,
+    pub category: u8,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct PausedChanged {
+    pub counter: Pubkey,
+    pub paused: bool,
+}
+
+#[event]
+#[derive(Debug)]
+pub struct CounterConfig {
+    pub counter: Pubkey,
     pub authority: Pubkey,
+    pub paused: bool,
+    pub overflow_mode: OverflowMode,
+    pub warn_margin: u64,
+    pub min_increment: u64,
+    pub decay_per_slot: u64,
+    pub decimals: u8,
+    pub tiers: [u64; 4],
+    pub allowed_program: Option<Pubkey>,
+    pub reward_mint: Option<Pubkey>,
+    pub reward_per_op: u64,
+    pub merkle_root: [u8; 32],
 }
 
 #[error_code]
@@ -106,4 +1510,225 @@ pub enum CounterError {
 
     #[msg("Unauthorized: Only the authority can perform this action")]
     Unauthorized,
+
+    #[msg("The provided Merkle proof is invalid")]
+    InvalidProof,
+
+    #[msg("This claim index has already been used")]
+    AlreadyClaimed,
+
+    #[msg("Not enough circulating value; the rest of the count is locked")]
+    InsufficientCirculating,
+
+    #[msg("Decimals must keep 10^decimals within u64")]
+    InvalidDecimals,
+
+    #[msg("Decimals can only be changed while the count is zero")]
+    DecimalsLocked,
+
+    #[msg("Tier thresholds must be non-zero and strictly increasing")]
+    InvalidTiers,
+
+    #[msg("Unauthorized: Caller program is not allowed to invoke this instruction")]
+    UnauthorizedProgram,
+
+    #[msg("The counter is paused")]
+    Paused,
+
+    #[msg("No named counter exists with that name")]
+    CounterNotFound,
+
+    #[msg("The multi-counter already holds the maximum number of named counters")]
+    TooManyCounters,
+
+    #[msg("Counter names must be non-empty and at most 32 bytes long")]
+    InvalidName,
+
+    #[msg("A named counter with that name already exists")]
+    DuplicateName,
+
+    #[msg("The amount is below the counter's minimum increment")]
+    BelowMinimum,
+
+    #[msg("The reward mint does not match the counter's configured reward mint")]
+    InvalidRewardMint,
+
+    #[msg("The new authority must not be the default public key")]
+    InvalidAuthority,
+
+    #[msg("A distinct source counter must be passed as the first remaining account")]
+    InvalidSource,
+
+    #[msg("Spend category is out of range")]
+    InvalidCategory,
+
+    #[msg("There is no pending reward to claim")]
+    NoPendingReward,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zeroed counter, as freshly allocated account data would decode
+    fn zeroed_counter() -> Counter {
+        Counter::deserialize(&mut &[0u8; Counter::INIT_SPACE][..]).unwrap()
+    }
+
+    #[test]
+    fn accumulator_at_adds_count_times_elapsed_slots() {
+        let mut counter = zeroed_counter();
+        counter.count = 7;
+        counter.accumulator = 100;
+        counter.last_slot = 10;
+
+        assert_eq!(counter.accumulator_at(10), 100);
+        assert_eq!(counter.accumulator_at(13), 121);
+        // a slot before the last accrual never rewinds the accumulator
+        assert_eq!(counter.accumulator_at(5), 100);
+
+        counter.count = u64::MAX;
+        counter.last_slot = 0;
+        assert_eq!(
+            counter.accumulator_at(u64::MAX),
+            100 + u64::MAX as u128 * u64::MAX as u128
+        );
+
+        counter.accumulator = u128::MAX;
+        counter.count = 2;
+        assert_eq!(counter.accumulator_at(1), 1);
+    }
+
+    fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        if a <= b {
+            keccak::hashv(&[&a, &b]).to_bytes()
+        } else {
+            keccak::hashv(&[&b, &a]).to_bytes()
+        }
+    }
+
+    #[test]
+    fn verify_proof_two_leaf_tree() {
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let leaf_a = claim_leaf(0, &alice, 10);
+        let leaf_b = claim_leaf(1, &bob, 20);
+        let root = hash_pair(leaf_a, leaf_b);
+
+        assert!(verify_proof(&[leaf_b], root, leaf_a));
+        assert!(verify_proof(&[leaf_a], root, leaf_b));
+        assert!(!verify_proof(&[leaf_b], root, claim_leaf(0, &alice, 11)));
+        assert!(!verify_proof(&[leaf_b], root, claim_leaf(0, &bob, 10)));
+    }
+
+    fn empty_leaderboard() -> Leaderboard {
+        Leaderboard {
+            bump: 0,
+            len: 0,
+            entries: [LeaderboardEntry {
+                authority: Pubkey::default(),
+                total: 0,
+            }; LEADERBOARD_SIZE],
+        }
+    }
+
+    fn standings(board: &Leaderboard) -> Vec<(Pubkey, u64)> {
+        board.entries[..board.len as usize]
+            .iter()
+            .map(|entry| (entry.authority, entry.total))
+            .collect()
+    }
+
+    #[test]
+    fn leaderboard_orders_entries_and_bubbles_up() {
+        let mut board = empty_leaderboard();
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        board.record(a, 5);
+        board.record(b, 10);
+        board.record(c, 7);
+        assert_eq!(standings(&board), vec![(b, 10), (c, 7), (a, 5)]);
+
+        board.record(a, 10);
+        assert_eq!(standings(&board), vec![(a, 15), (b, 10), (c, 7)]);
+
+        board.record(c, 3);
+        assert_eq!(standings(&board), vec![(a, 15), (b, 10), (c, 10)]);
+    }
+
+    #[test]
+    fn full_leaderboard_only_admits_entries_beating_the_last() {
+        let mut board = empty_leaderboard();
+        let authorities: Vec<Pubkey> = (0..LEADERBOARD_SIZE)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for (rank, authority) in authorities.iter().enumerate() {
+            board.record(*authority, 100 - rank as u64);
+        }
+        let full = standings(&board);
+        assert_eq!(full.len(), LEADERBOARD_SIZE);
+
+        board.record(Pubkey::new_unique(), 91);
+        assert_eq!(standings(&board), full);
+
+        let newcomer = Pubkey::new_unique();
+        board.record(newcomer, 95);
+        let standings = standings(&board);
+        assert_eq!(standings.len(), LEADERBOARD_SIZE);
+        assert_eq!(standings[5], (authorities[5], 95));
+        assert_eq!(standings[6], (newcomer, 95));
+        assert_eq!(standings[9], (authorities[8], 92));
+        assert!(!standings.contains(&(authorities[9], 91)));
+    }
+
+    #[test]
+    fn baseline_error_codes_are_stable() {
+        assert_eq!(u32::from(CounterError::InvalidAmount), 6000);
+        assert_eq!(u32::from(CounterError::Overflow), 6001);
+        assert_eq!(u32::from(CounterError::Underflow), 6002);
+        assert_eq!(u32::from(CounterError::Unauthorized), 6003);
+    }
+
+    #[test]
+    fn invalid_name_message_matches_max_name_len() {
+        let message = CounterError::InvalidName.to_string();
+        assert!(message.contains(&format!("at most {MAX_NAME_LEN} bytes")));
+    }
+
+    #[test]
+    fn tier_thresholds_validation() {
+        assert!(valid_tiers(&[1, 2, 3, 4]));
+        assert!(valid_tiers(&[10, 100, 1_000, u64::MAX]));
+        assert!(!valid_tiers(&[0, 1, 2, 3]));
+        assert!(!valid_tiers(&[1, 1, 2, 3]));
+        assert!(!valid_tiers(&[1, 2, 3, 3]));
+        assert!(!valid_tiers(&[4, 3, 2, 1]));
+    }
+
+    #[test]
+    fn claim_bitmap_round_trip() {
+        let mut claims = ClaimBitmap {
+            counter: Pubkey::new_unique(),
+            merkle_root: [1; 32],
+            bump: 0,
+            claimed: [0; MAX_CLAIMS / 8],
+        };
+        let last = (MAX_CLAIMS - 1) as u32;
+
+        assert!(!claims.is_claimed(0));
+        claims.set_claimed(0);
+        claims.set_claimed(9);
+        claims.set_claimed(last);
+
+        assert!(claims.is_claimed(0));
+        assert!(claims.is_claimed(9));
+        assert!(claims.is_claimed(last));
+        assert!(!claims.is_claimed(1));
+        assert!(!claims.is_claimed(8));
+        assert!(!claims.is_claimed(last - 1));
+    }
 }
@@ -0,0 +1,1708 @@
+use std::sync::Once;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::{AccountInfo, Pubkey};
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::program_pack::Pack;
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::solana_program::system_instruction;
+use anchor_lang::{
+    system_program, AccountDeserialize, AnchorDeserialize, Event, InstructionData, ToAccountMetas,
+};
+use anchor_spl::token::{spl_token, TokenAccount};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use counter_program::{
+    accounts, claim_leaf, instruction, AuthorityChanged, Counter, CounterConfig, CounterError,
+    CounterInitialized, CounterReset, Drained, IdempotencyKey, IncrementTagged, Leaderboard,
+    NamedCounter, NamedCountersListed, NearCapacity, OverflowMode, TierChanged, SPEND_CATEGORIES,
+};
+use solana_program_test::{
+    processor, BanksTransactionResultWithMetadata, ProgramTest, ProgramTestContext,
+};
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program::invoke;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::sysvar;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Anchor's entrypoint wants the account slice and its infos to share a lifetime
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    counter_program::entry(program_id, accounts, data)
+}
+
+/// Stand-in for a third-party program: forwards its data as the amount of an
+/// `increment_via_cpi` on the counter passed as the first account
+fn process_caller(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let amount = u64::from_le_bytes(data.try_into().unwrap());
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: counter_program::accounts::IncrementViaCpi {
+            counter: *accounts[0].key,
+            instructions: *accounts[1].key,
+        }
+        .to_account_metas(None),
+        data: instruction::IncrementViaCpi { amount }.data(),
+    };
+    invoke(&ix, &accounts[..2])
+}
+
+/// Forwards to the program-test stubs, but records `sol_log_data` (and so `emit!`)
+/// in the transaction logs the way the SBF runtime does
+struct EventLogStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for EventLogStubs {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| STANDARD.encode(field)).collect();
+        self.0
+            .sol_log(&format!("Program data: {}", fields.join(" ")))
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+/// Default stubs, only installed while swapping in `EventLogStubs`
+struct Placeholder;
+
+impl SyscallStubs for Placeholder {}
+
+fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "counter_program",
+        counter_program::ID,
+        processor!(process_instruction),
+    )
+}
+
+struct TestEnv {
+    ctx: ProgramTestContext,
+}
+
+impl TestEnv {
+    async fn new() -> Self {
+        Self::start(program_test()).await
+    }
+
+    async fn start(program_test: ProgramTest) -> Self {
+        let ctx = program_test.start_with_context().await;
+
+        // program-test installs its own stubs while starting; wrap them exactly once
+        static WRAP_STUBS: Once = Once::new();
+        WRAP_STUBS.call_once(|| {
+            let inner = program_stubs::set_syscall_stubs(Box::new(Placeholder));
+            program_stubs::set_syscall_stubs(Box::new(EventLogStubs(inner)));
+        });
+
+        Self { ctx }
+    }
+
+    fn payer(&self) -> Keypair {
+        self.ctx.payer.insecure_clone()
+    }
+
+    /// Send `instructions` in a fresh transaction paid for by the payer
+    async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> BanksTransactionResultWithMetadata {
+        let blockhash = self.ctx.get_new_latest_blockhash().await.unwrap();
+        let payer = self.payer();
+        let mut all_signers = vec![&payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        self.ctx
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap()
+    }
+
+    /// Create a counter owned by the payer
+    async fn create_counter(&mut self) -> Keypair {
+        let (counter, ix) = self.initialize(instruction::Initialize {});
+        assert_ok(&self.send(&[ix], &[&counter]).await);
+        counter
+    }
+
+    /// Build an instruction using the `Initialize` accounts for a new counter keypair
+    fn initialize(&self, data: impl InstructionData) -> (Keypair, Instruction) {
+        let counter = Keypair::new();
+        let ix = Instruction {
+            program_id: counter_program::ID,
+            accounts: accounts::Initialize {
+                counter: counter.pubkey(),
+                authority: self.ctx.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: data.data(),
+        };
+        (counter, ix)
+    }
+
+    /// Build an instruction using the `Update` accounts (counter + payer as authority)
+    fn update(&self, counter: &Pubkey, data: impl InstructionData) -> Instruction {
+        Instruction {
+            program_id: counter_program::ID,
+            accounts: accounts::Update {
+                counter: *counter,
+                authority: self.ctx.payer.pubkey(),
+            }
+            .to_account_metas(None),
+            data: data.data(),
+        }
+    }
+
+    async fn counter(&mut self, counter: &Pubkey) -> Counter {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*counter)
+            .await
+            .unwrap()
+            .expect("counter account exists");
+        Counter::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+}
+
+fn assert_ok(result: &BanksTransactionResultWithMetadata) {
+    assert_eq!(result.result, Ok(()), "logs: {:#?}", result.metadata);
+}
+
+fn assert_error(result: &BanksTransactionResultWithMetadata, error: CounterError) {
+    match &result.result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+            assert_eq!(*code, u32::from(error), "logs: {:#?}", result.metadata)
+        }
+        other => panic!("expected {error:?}, got {other:?}"),
+    }
+}
+
+/// Decode every `T` event emitted by the transaction
+fn events<T: Event>(result: &BanksTransactionResultWithMetadata) -> Vec<T> {
+    let logs = &result.metadata.as_ref().unwrap().log_messages;
+    logs.iter()
+        .filter_map(|log| log.split_once("Program data: "))
+        .filter_map(|(_, data)| STANDARD.decode(data).ok())
+        .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+        .map(|data| T::deserialize(&mut &data[8..]).unwrap())
+        .collect()
+}
+
+fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    if a <= b {
+        keccak::hashv(&[&a, &b]).to_bytes()
+    } else {
+        keccak::hashv(&[&b, &a]).to_bytes()
+    }
+}
+
+/// Set `root` on the counter and create its claim bitmap, returning the bitmap PDA
+async fn start_distribution(env: &mut TestEnv, counter: &Pubkey, root: [u8; 32]) -> Pubkey {
+    let (claims, _) = Pubkey::find_program_address(
+        &[b"claims", counter.as_ref(), root.as_ref()],
+        &counter_program::ID,
+    );
+    let set_root = env.update(counter, instruction::SetMerkleRoot { merkle_root: root });
+    let init_claims = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::InitializeClaims {
+            counter: *counter,
+            claims,
+            authority: env.payer().pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeClaims {}.data(),
+    };
+    assert_ok(&env.send(&[set_root, init_claims], &[]).await);
+    claims
+}
+
+fn claim_increment(
+    counter: &Pubkey,
+    claims: &Pubkey,
+    claimant: &Pubkey,
+    index: u32,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+) -> Instruction {
+    Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::ClaimIncrement {
+            counter: *counter,
+            claims: *claims,
+            claimant: *claimant,
+        }
+        .to_account_metas(None),
+        data: instruction::ClaimIncrement {
+            index,
+            amount,
+            proof,
+        }
+        .data(),
+    }
+}
+
+#[tokio::test]
+async fn claim_increment_rejects_reused_index() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let leaf_a = claim_leaf(0, &alice.pubkey(), 10);
+    let leaf_b = claim_leaf(1, &bob.pubkey(), 20);
+    let claims = start_distribution(&mut env, &counter, hash_pair(leaf_a, leaf_b)).await;
+
+    let claim = claim_increment(&counter, &claims, &alice.pubkey(), 0, 10, vec![leaf_b]);
+    assert_ok(&env.send(std::slice::from_ref(&claim), &[&alice]).await);
+    assert_eq!(env.counter(&counter).await.count, 10);
+
+    let result = env.send(&[claim], &[&alice]).await;
+    assert_error(&result, CounterError::AlreadyClaimed);
+    assert_eq!(env.counter(&counter).await.count, 10);
+
+    // bob's leaf cannot be redeemed by alice
+    let claim = claim_increment(&counter, &claims, &alice.pubkey(), 1, 20, vec![leaf_a]);
+    assert_error(
+        &env.send(&[claim], &[&alice]).await,
+        CounterError::InvalidProof,
+    );
+}
+
+#[tokio::test]
+async fn claim_increment_ignores_a_later_min_increment() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let alice = Keypair::new();
+    let filler = claim_leaf(1, &Pubkey::new_unique(), 1);
+    let leaf = claim_leaf(0, &alice.pubkey(), 10);
+    let claims = start_distribution(&mut env, &counter, hash_pair(leaf, filler)).await;
+
+    let ix = env.update(&counter, instruction::SetMinIncrement { min_increment: 50 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let claim = claim_increment(&counter, &claims, &alice.pubkey(), 0, 10, vec![filler]);
+    assert_ok(&env.send(&[claim], &[&alice]).await);
+    assert_eq!(env.counter(&counter).await.count, 10);
+
+    // ordinary increments still honour the minimum
+    let ix = env.update(&counter, instruction::Increment { amount: 10 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::BelowMinimum);
+}
+
+#[tokio::test]
+async fn rotating_the_root_starts_a_fresh_bitmap() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let alice = Keypair::new();
+    let filler = claim_leaf(1, &Pubkey::new_unique(), 1);
+
+    let first = claim_leaf(0, &alice.pubkey(), 10);
+    let claims = start_distribution(&mut env, &counter, hash_pair(first, filler)).await;
+    let claim = claim_increment(&counter, &claims, &alice.pubkey(), 0, 10, vec![filler]);
+    assert_ok(&env.send(&[claim], &[&alice]).await);
+
+    let second = claim_leaf(0, &alice.pubkey(), 5);
+    let next_claims = start_distribution(&mut env, &counter, hash_pair(second, filler)).await;
+    assert_ne!(next_claims, claims);
+
+    // the old bitmap no longer matches the counter's root
+    let claim = claim_increment(&counter, &claims, &alice.pubkey(), 0, 5, vec![filler]);
+    let result = env.send(&[claim], &[&alice]).await;
+    assert_eq!(
+        result.result,
+        Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(ErrorCode::ConstraintSeeds.into())
+        ))
+    );
+
+    let claim = claim_increment(&counter, &next_claims, &alice.pubkey(), 0, 5, vec![filler]);
+    assert_ok(&env.send(&[claim], &[&alice]).await);
+    assert_eq!(env.counter(&counter).await.count, 15);
+}
+
+fn assert_supply(counter: &Counter, locked: u64, circulating: u64) {
+    assert_eq!(counter.locked, locked);
+    assert_eq!(counter.circulating, circulating);
+    assert_eq!(counter.count, counter.locked + counter.circulating);
+}
+
+#[tokio::test]
+async fn lock_and_unlock_keep_supply_split() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(&counter, instruction::Increment { amount: 10 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::Lock { amount: 4 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 4, 6);
+
+    let ix = env.update(&counter, instruction::Decrement { amount: 7 });
+    assert_error(
+        &env.send(&[ix], &[]).await,
+        CounterError::InsufficientCirculating,
+    );
+    let ix = env.update(&counter, instruction::Lock { amount: 7 });
+    assert_error(
+        &env.send(&[ix], &[]).await,
+        CounterError::InsufficientCirculating,
+    );
+    let ix = env.update(&counter, instruction::Decrement { amount: 11 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Underflow);
+    assert_supply(&env.counter(&counter).await, 4, 6);
+
+    let ix = env.update(&counter, instruction::Decrement { amount: 6 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 4, 0);
+
+    let ix = env.update(&counter, instruction::Unlock { amount: 5 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Underflow);
+    let ix = env.update(&counter, instruction::Unlock { amount: 3 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 1, 3);
+}
+
+#[tokio::test]
+async fn increment_scaled_uses_configured_decimals() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(&counter, instruction::SetDecimals { decimals: 1 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::IncrementScaled { whole: 1, frac: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 15);
+
+    let ix = env.update(
+        &counter,
+        instruction::IncrementScaled { whole: 1, frac: 10 },
+    );
+    assert_error(&env.send(&[ix], &[]).await, CounterError::InvalidAmount);
+
+    let ix = env.update(&counter, instruction::SetDecimals { decimals: 2 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::DecimalsLocked);
+    let counter = env.counter(&counter).await;
+    assert_eq!((counter.count, counter.decimals), (15, 1));
+}
+
+#[tokio::test]
+async fn crossing_a_tier_emits_tier_changed() {
+    let mut env = TestEnv::new().await;
+    let (counter, ix) = env.initialize(instruction::InitializeTiered {
+        tiers: [10, 20, 30, 40],
+    });
+    assert_ok(&env.send(&[ix], &[&counter]).await);
+    let counter = counter.pubkey();
+
+    let ix = env.update(&counter, instruction::Increment { amount: 9 });
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    assert!(events::<TierChanged>(&result).is_empty());
+
+    let ix = env.update(&counter, instruction::Increment { amount: 12 });
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let changes = events::<TierChanged>(&result);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].counter, counter);
+    assert_eq!((changes[0].previous_tier, changes[0].tier), (0, 2));
+    assert_eq!(env.counter(&counter).await.current_tier(), 2);
+}
+
+#[tokio::test]
+async fn initialize_tiered_rejects_invalid_thresholds() {
+    let mut env = TestEnv::new().await;
+    for tiers in [[0, 1, 2, 3], [5, 5, 6, 7]] {
+        let (counter, ix) = env.initialize(instruction::InitializeTiered { tiers });
+        assert_error(
+            &env.send(&[ix], &[&counter]).await,
+            CounterError::InvalidTiers,
+        );
+    }
+}
+
+#[tokio::test]
+async fn increment_via_cpi_only_accepts_the_allowed_program() {
+    let allowed = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let mut program_test = program_test();
+    program_test.add_program("allowed_caller", allowed, processor!(process_caller));
+    program_test.add_program("other_caller", other, processor!(process_caller));
+    let mut env = TestEnv::start(program_test).await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(
+        &counter,
+        instruction::SetAllowedProgram {
+            allowed_program: Some(allowed),
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let call_from = |caller: Pubkey| Instruction {
+        program_id: caller,
+        accounts: vec![
+            AccountMeta::new(counter, false),
+            AccountMeta::new_readonly(sysvar::instructions::ID, false),
+            AccountMeta::new_readonly(counter_program::ID, false),
+        ],
+        data: 5u64.to_le_bytes().to_vec(),
+    };
+    assert_ok(&env.send(&[call_from(allowed)], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 5);
+
+    let result = env.send(&[call_from(other)], &[]).await;
+    assert_error(&result, CounterError::UnauthorizedProgram);
+
+    let direct = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::IncrementViaCpi {
+            counter,
+            instructions: sysvar::instructions::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::IncrementViaCpi { amount: 5 }.data(),
+    };
+    assert_error(
+        &env.send(&[direct], &[]).await,
+        CounterError::UnauthorizedProgram,
+    );
+    assert_eq!(env.counter(&counter).await.count, 5);
+}
+
+#[tokio::test]
+async fn increment_idempotent_applies_a_key_once() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let authority = env.payer().pubkey();
+    let record = |key: [u8; 16]| {
+        Pubkey::find_program_address(
+            &[b"idempotency", counter.as_ref(), key.as_ref()],
+            &counter_program::ID,
+        )
+        .0
+    };
+    let increment = |amount: u64, key: [u8; 16]| Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::IncrementIdempotent {
+            counter,
+            idempotency_key: record(key),
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::IncrementIdempotent { amount, key }.data(),
+    };
+    assert_ok(&env.send(&[increment(3, [1; 16])], &[]).await);
+    assert_ok(&env.send(&[increment(3, [1; 16])], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 3);
+
+    let account = env
+        .ctx
+        .banks_client
+        .get_account(record([1; 16]))
+        .await
+        .unwrap()
+        .unwrap();
+    let used = IdempotencyKey::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((used.counter, used.key), (counter, [1; 16]));
+
+    let result = env.send(&[increment(0, [2; 16])], &[]).await;
+    assert_error(&result, CounterError::InvalidAmount);
+
+    assert_ok(&env.send(&[increment(4, [2; 16])], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 7);
+
+    // keys never expire, so a retry re-signed much later is still a no-op
+    let last_slot = env.counter(&counter).await.last_slot;
+    env.ctx.warp_to_slot(last_slot + 10_000).unwrap();
+    assert_ok(&env.send(&[increment(3, [1; 16])], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 7);
+}
+
+#[tokio::test]
+async fn paused_counter_rejects_updates_until_resumed() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(&counter, instruction::Increment { amount: 4 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::SetPaused { paused: true });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let ix = env.update(&counter, instruction::Increment { amount: 1 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Paused);
+    let ix = env.update(&counter, instruction::Decrement { amount: 1 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Paused);
+    assert_eq!(env.counter(&counter).await.count, 4);
+
+    let ix = env.update(&counter, instruction::Reset {});
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 0);
+
+    let ix = env.update(&counter, instruction::SetPaused { paused: false });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::Increment { amount: 2 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::Decrement { amount: 1 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 1);
+}
+
+#[tokio::test]
+async fn overflow_mode_decides_what_happens_past_u64_max() {
+    let mut env = TestEnv::new().await;
+
+    let strict = env.create_counter().await.pubkey();
+    let ix = env.update(&strict, instruction::Increment { amount: u64::MAX });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&strict, instruction::Increment { amount: 1 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Overflow);
+    assert_eq!(env.counter(&strict).await.count, u64::MAX);
+
+    let ring = env.create_counter().await.pubkey();
+    let ix = env.update(
+        &ring,
+        instruction::Increment {
+            amount: u64::MAX - 1,
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&ring, instruction::Lock { amount: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(
+        &ring,
+        instruction::SetOverflowMode {
+            overflow_mode: OverflowMode::Wrap,
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&ring, instruction::Increment { amount: 3 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let ring = env.counter(&ring).await;
+    assert_eq!(ring.count, 1);
+    assert_supply(&ring, 0, 1);
+}
+
+/// Borsh-decode the instruction's return data
+fn return_data<T: AnchorDeserialize>(result: &BanksTransactionResultWithMetadata) -> T {
+    let return_data = result.metadata.as_ref().unwrap().return_data.as_ref();
+    let return_data = return_data.expect("instruction returned data");
+    assert_eq!(return_data.program_id, counter_program::ID);
+    T::try_from_slice(&return_data.data).unwrap()
+}
+
+#[tokio::test]
+async fn increment_or_init_reports_creation_once() {
+    let mut env = TestEnv::new().await;
+    let authority = env.payer().pubkey();
+    let (counter, _) =
+        Pubkey::find_program_address(&[b"counter", authority.as_ref()], &counter_program::ID);
+    let increment_or_init = |amount: u64| Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::IncrementOrInit {
+            counter,
+            authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::IncrementOrInit { amount }.data(),
+    };
+
+    let first = env.send(&[increment_or_init(2)], &[]).await;
+    assert_ok(&first);
+    assert!(return_data::<bool>(&first));
+    let initialized = events::<CounterInitialized>(&first);
+    assert_eq!(initialized.len(), 1);
+    assert_eq!(
+        (initialized[0].counter, initialized[0].authority),
+        (counter, authority)
+    );
+
+    let second = env.send(&[increment_or_init(3)], &[]).await;
+    assert_ok(&second);
+    assert!(!return_data::<bool>(&second));
+    assert!(events::<CounterInitialized>(&second).is_empty());
+    assert_eq!(env.counter(&counter).await.count, 5);
+}
+
+#[tokio::test]
+async fn multiply_fixed_rounds_down_and_keeps_locked_floor() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let precision = counter_program::PRECISION;
+
+    let ix = env.update(&counter, instruction::Increment { amount: 7 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(
+        &counter,
+        instruction::MultiplyFixed {
+            factor_scaled: precision * 3 / 2,
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 0, 10);
+
+    let ix = env.update(&counter, instruction::Increment { amount: 1 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(
+        &counter,
+        instruction::MultiplyFixed {
+            factor_scaled: precision / 2,
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 0, 5);
+
+    let ix = env.update(&counter, instruction::Lock { amount: 4 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(
+        &counter,
+        instruction::MultiplyFixed {
+            factor_scaled: precision / 2,
+        },
+    );
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Underflow);
+    assert_supply(&env.counter(&counter).await, 4, 1);
+}
+
+/// Create a `MultiCounter` owned by the payer
+async fn create_multi(env: &mut TestEnv) -> Pubkey {
+    let multi = Keypair::new();
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::InitializeMulti {
+            multi_counter: multi.pubkey(),
+            authority: env.payer().pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeMulti {}.data(),
+    };
+    assert_ok(&env.send(&[ix], &[&multi]).await);
+    multi.pubkey()
+}
+
+fn update_multi(multi: &Pubkey, authority: &Pubkey, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::UpdateMulti {
+            multi_counter: *multi,
+            authority: *authority,
+        }
+        .to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+#[tokio::test]
+async fn named_counters_add_increment_and_read() {
+    let mut env = TestEnv::new().await;
+    let multi = create_multi(&mut env).await;
+    let authority = env.payer().pubkey();
+
+    let add = |name: &str| {
+        update_multi(
+            &multi,
+            &authority,
+            instruction::AddNamed { name: name.into() },
+        )
+    };
+    let increment = |name: &str, amount: u64| {
+        let name = name.into();
+        update_multi(
+            &multi,
+            &authority,
+            instruction::IncrementNamed { name, amount },
+        )
+    };
+    let get = |name: &str| Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::ReadMulti {
+            multi_counter: multi,
+        }
+        .to_account_metas(None),
+        data: instruction::GetNamed { name: name.into() }.data(),
+    };
+
+    assert_ok(&env.send(&[add("apples"), add("pears")], &[]).await);
+    assert_ok(&env.send(&[increment("apples", 3)], &[]).await);
+    assert_ok(&env.send(&[increment("apples", 4)], &[]).await);
+
+    let result = env.send(&[get("apples")], &[]).await;
+    assert_ok(&result);
+    assert_eq!(return_data::<u64>(&result), 7);
+    let result = env.send(&[get("pears")], &[]).await;
+    assert_ok(&result);
+    assert_eq!(return_data::<u64>(&result), 0);
+
+    let result = env.send(&[increment("plums", 1)], &[]).await;
+    assert_error(&result, CounterError::CounterNotFound);
+    assert_error(
+        &env.send(&[add("pears")], &[]).await,
+        CounterError::DuplicateName,
+    );
+    assert_error(&env.send(&[add("")], &[]).await, CounterError::InvalidName);
+    let too_long = "x".repeat(counter_program::MAX_NAME_LEN + 1);
+    assert_error(
+        &env.send(&[add(&too_long)], &[]).await,
+        CounterError::InvalidName,
+    );
+    let longest = "x".repeat(counter_program::MAX_NAME_LEN);
+    assert_ok(&env.send(&[add(&longest)], &[]).await);
+}
+
+#[tokio::test]
+async fn accrue_folds_count_into_accumulator_per_slot() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(&counter, instruction::Increment { amount: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let before = env.counter(&counter).await;
+
+    env.ctx.warp_to_slot(before.last_slot + 10).unwrap();
+    let ix = env.update(&counter, instruction::Increment { amount: 1 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let after = env.counter(&counter).await;
+    let elapsed = after.last_slot - before.last_slot;
+    assert!(elapsed >= 10);
+    assert_eq!(after.accumulator, before.accumulator + 5 * elapsed as u128);
+    assert_eq!(after.count, 6);
+}
+
+fn sum_into(
+    destination: &Pubkey,
+    source: &Pubkey,
+    authority: &Pubkey,
+    move_source: bool,
+) -> Instruction {
+    let mut accounts = accounts::SumInto {
+        destination: *destination,
+        authority: *authority,
+    }
+    .to_account_metas(None);
+    accounts.push(AccountMeta::new(*source, false));
+    Instruction {
+        program_id: counter_program::ID,
+        accounts,
+        data: instruction::SumInto { move_source }.data(),
+    }
+}
+
+#[tokio::test]
+async fn sum_into_adds_source_and_optionally_zeroes_it() {
+    let mut env = TestEnv::new().await;
+    let destination = env.create_counter().await.pubkey();
+    let source = env.create_counter().await.pubkey();
+
+    let ix = env.update(&destination, instruction::Increment { amount: 2 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&source, instruction::Increment { amount: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let authority = env.payer().pubkey();
+    let sum_into = |move_source| sum_into(&destination, &source, &authority, move_source);
+
+    assert_ok(&env.send(&[sum_into(false)], &[]).await);
+    assert_eq!(env.counter(&destination).await.count, 7);
+    assert_eq!(env.counter(&source).await.count, 5);
+
+    assert_ok(&env.send(&[sum_into(true)], &[]).await);
+    assert_eq!(env.counter(&destination).await.count, 12);
+    assert_supply(&env.counter(&source).await, 0, 0);
+
+    // an empty source is skipped rather than tripping min_increment
+    let ix = env.update(
+        &destination,
+        instruction::SetMinIncrement { min_increment: 3 },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_ok(&env.send(&[sum_into(true)], &[]).await);
+    assert_eq!(env.counter(&destination).await.count, 12);
+    assert_supply(&env.counter(&source).await, 0, 0);
+}
+
+/// Reward mint whose authority is a counter's `["reward", counter]` PDA, plus a
+/// payer-owned token account for it
+struct Reward {
+    mint: Pubkey,
+    account: Pubkey,
+    authority: Pubkey,
+}
+
+impl TestEnv {
+    /// Create a reward mint for `counter` and configure it as the counter's reward mint
+    async fn setup_reward(&mut self, counter: &Pubkey) -> Reward {
+        let payer = self.payer().pubkey();
+        let (authority, _) =
+            Pubkey::find_program_address(&[b"reward", counter.as_ref()], &counter_program::ID);
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let mint = Keypair::new();
+        let account = Keypair::new();
+        let setup = [
+            system_instruction::create_account(
+                &payer,
+                &mint.pubkey(),
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_mint2(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &authority,
+                None,
+                0,
+            )
+            .unwrap(),
+            system_instruction::create_account(
+                &payer,
+                &account.pubkey(),
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::ID,
+                &account.pubkey(),
+                &mint.pubkey(),
+                &payer,
+            )
+            .unwrap(),
+            self.update(
+                counter,
+                instruction::SetRewardMint {
+                    reward_mint: Some(mint.pubkey()),
+                },
+            ),
+        ];
+        assert_ok(&self.send(&setup, &[&mint, &account]).await);
+        Reward {
+            mint: mint.pubkey(),
+            account: account.pubkey(),
+            authority,
+        }
+    }
+
+    /// Build an instruction using the `UpdateWithReward` accounts
+    fn with_reward(
+        &self,
+        counter: &Pubkey,
+        reward: &Reward,
+        data: impl InstructionData,
+    ) -> Instruction {
+        Instruction {
+            program_id: counter_program::ID,
+            accounts: accounts::UpdateWithReward {
+                counter: *counter,
+                authority: self.ctx.payer.pubkey(),
+                reward_mint: reward.mint,
+                reward_account: reward.account,
+                reward_authority: reward.authority,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: data.data(),
+        }
+    }
+
+    async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*account)
+            .await
+            .unwrap()
+            .expect("token account exists");
+        TokenAccount::try_deserialize(&mut account.data.as_slice())
+            .unwrap()
+            .amount
+    }
+}
+
+#[tokio::test]
+async fn claim_reward_mints_reward_per_op_for_each_operation() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let reward = env.setup_reward(&counter).await;
+    let ix = env.update(&counter, instruction::SetRewardPerOp { reward_per_op: 3 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let ops = [
+        env.update(&counter, instruction::Increment { amount: 4 }),
+        env.update(&counter, instruction::Lock { amount: 2 }),
+        env.update(&counter, instruction::Unlock { amount: 1 }),
+        env.update(
+            &counter,
+            instruction::MultiplyFixed {
+                factor_scaled: 2 * counter_program::PRECISION,
+            },
+        ),
+        env.update(&counter, instruction::ApplyDecay {}),
+        env.update(&counter, instruction::Decrement { amount: 1 }),
+        env.update(&counter, instruction::Drain {}),
+        env.update(&counter, instruction::Reset {}),
+    ];
+    assert_ok(&env.send(&ops, &[]).await);
+    let expected = ops.len() as u64 * 3;
+    assert_eq!(env.counter(&counter).await.pending_reward, expected);
+
+    let claim = env.with_reward(&counter, &reward, instruction::ClaimReward {});
+    assert_ok(&env.send(std::slice::from_ref(&claim), &[]).await);
+    assert_eq!(env.token_balance(&reward.account).await, expected);
+    assert_eq!(env.counter(&counter).await.pending_reward, 0);
+
+    assert_error(
+        &env.send(&[claim], &[]).await,
+        CounterError::NoPendingReward,
+    );
+}
+
+#[tokio::test]
+async fn increment_with_reward_mints_the_amount() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let reward = env.setup_reward(&counter).await;
+
+    let ix = env.with_reward(
+        &counter,
+        &reward,
+        instruction::IncrementWithReward { amount: 4 },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.with_reward(
+        &counter,
+        &reward,
+        instruction::IncrementWithReward { amount: 6 },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 10);
+    assert_eq!(env.token_balance(&reward.account).await, 10);
+
+    // a mint configured for another counter is refused
+    let other = env.create_counter().await.pubkey();
+    let other_reward = env.setup_reward(&other).await;
+    let ix = env.with_reward(
+        &counter,
+        &other_reward,
+        instruction::IncrementWithReward { amount: 1 },
+    );
+    assert_error(&env.send(&[ix], &[]).await, CounterError::InvalidRewardMint);
+    assert_eq!(env.counter(&counter).await.count, 10);
+    assert_eq!(env.token_balance(&other_reward.account).await, 0);
+}
+
+fn logs(result: &BanksTransactionResultWithMetadata) -> &[String] {
+    &result.metadata.as_ref().unwrap().log_messages
+}
+
+#[tokio::test]
+async fn describe_many_logs_each_counter_and_skips_others() {
+    let mut env = TestEnv::new().await;
+    let authority = env.payer().pubkey();
+    let mut counters = Vec::new();
+    for amount in [3, 5, 8] {
+        let counter = env.create_counter().await.pubkey();
+        let ix = env.update(&counter, instruction::Increment { amount });
+        assert_ok(&env.send(&[ix], &[]).await);
+        counters.push((counter, amount));
+    }
+
+    let mut metas: Vec<AccountMeta> = counters
+        .iter()
+        .map(|(counter, _)| AccountMeta::new_readonly(*counter, false))
+        .collect();
+    metas.push(AccountMeta::new_readonly(authority, false));
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: metas,
+        data: instruction::DescribeMany {}.data(),
+    };
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+
+    let logs = logs(&result);
+    for (counter, count) in counters {
+        let line =
+            format!("Program log: {{ pubkey: {counter}, count: {count}, authority: {authority} }}");
+        assert!(logs.contains(&line), "missing {line} in {logs:#?}");
+    }
+    let skip = format!("Program log: Skipping {authority}: not a counter account");
+    assert!(logs.contains(&skip), "missing {skip} in {logs:#?}");
+}
+
+#[tokio::test]
+async fn multiply_fixed_reports_tier_and_capacity_changes() {
+    let mut env = TestEnv::new().await;
+    let precision = counter_program::PRECISION;
+
+    let (tiered, ix) = env.initialize(instruction::InitializeTiered {
+        tiers: [10, 20, 30, 40],
+    });
+    assert_ok(&env.send(&[ix], &[&tiered]).await);
+    let tiered = tiered.pubkey();
+    let ix = env.update(&tiered, instruction::Increment { amount: 8 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(
+        &tiered,
+        instruction::MultiplyFixed {
+            factor_scaled: 3 * precision,
+        },
+    );
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let changes = events::<TierChanged>(&result);
+    assert_eq!(changes.len(), 1);
+    assert_eq!((changes[0].previous_tier, changes[0].tier), (0, 2));
+
+    let counter = env.create_counter().await.pubkey();
+    let quarter = u64::MAX / 4;
+    let setup = [
+        env.update(&counter, instruction::Increment { amount: quarter }),
+        env.update(
+            &counter,
+            instruction::SetWarnMargin {
+                warn_margin: u64::MAX / 2,
+            },
+        ),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+    let ix = env.update(
+        &counter,
+        instruction::MultiplyFixed {
+            factor_scaled: 3 * precision,
+        },
+    );
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let warnings = events::<NearCapacity>(&result);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].remaining, u64::MAX - 3 * quarter);
+}
+
+#[tokio::test]
+async fn describe_config_emits_every_field() {
+    let mut env = TestEnv::new().await;
+    let tiers = [5, 50, 500, 5_000];
+    let (counter, ix) = env.initialize(instruction::InitializeTiered { tiers });
+    assert_ok(&env.send(&[ix], &[&counter]).await);
+    let counter = counter.pubkey();
+    let allowed_program = Pubkey::new_unique();
+    let reward_mint = Pubkey::new_unique();
+
+    let setup = [
+        env.update(&counter, instruction::SetDecimals { decimals: 4 }),
+        env.update(
+            &counter,
+            instruction::SetOverflowMode {
+                overflow_mode: OverflowMode::Wrap,
+            },
+        ),
+        env.update(&counter, instruction::SetWarnMargin { warn_margin: 7 }),
+        env.update(&counter, instruction::SetMinIncrement { min_increment: 3 }),
+        env.update(&counter, instruction::SetDecayPerSlot { decay_per_slot: 2 }),
+        env.update(
+            &counter,
+            instruction::SetAllowedProgram {
+                allowed_program: Some(allowed_program),
+            },
+        ),
+        env.update(
+            &counter,
+            instruction::SetRewardMint {
+                reward_mint: Some(reward_mint),
+            },
+        ),
+        env.update(&counter, instruction::SetRewardPerOp { reward_per_op: 9 }),
+        env.update(
+            &counter,
+            instruction::SetMerkleRoot {
+                merkle_root: [7; 32],
+            },
+        ),
+        env.update(&counter, instruction::SetPaused { paused: true }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::ReadOnly { counter }.to_account_metas(None),
+        data: instruction::DescribeConfig {}.data(),
+    };
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let configs = events::<CounterConfig>(&result);
+    assert_eq!(configs.len(), 1);
+    let config = &configs[0];
+    assert_eq!(config.counter, counter);
+    assert_eq!(config.authority, env.payer().pubkey());
+    assert!(config.paused);
+    assert_eq!(config.overflow_mode, OverflowMode::Wrap);
+    assert_eq!(config.warn_margin, 7);
+    assert_eq!(config.min_increment, 3);
+    assert_eq!(config.decay_per_slot, 2);
+    assert_eq!(config.decimals, 4);
+    assert_eq!(config.tiers, tiers);
+    assert_eq!(config.allowed_program, Some(allowed_program));
+    assert_eq!(config.reward_mint, Some(reward_mint));
+    assert_eq!(config.reward_per_op, 9);
+    assert_eq!(config.merkle_root, [7; 32]);
+}
+
+#[tokio::test]
+async fn drain_reports_prior_count_and_zeroes_the_counter() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let setup = [
+        env.update(&counter, instruction::Increment { amount: 12 }),
+        env.update(&counter, instruction::Lock { amount: 5 }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+    let ix = env.update(&counter, instruction::Drain {});
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+
+    let drained = events::<Drained>(&result);
+    assert_eq!(drained.len(), 1);
+    assert_eq!((drained[0].counter, drained[0].amount), (counter, 12));
+    assert_supply(&env.counter(&counter).await, 0, 0);
+}
+
+#[tokio::test]
+async fn multi_counter_rejects_names_past_capacity() {
+    let mut env = TestEnv::new().await;
+    let multi = create_multi(&mut env).await;
+    let authority = env.payer().pubkey();
+    let add = |n: usize| {
+        let name = format!("counter-{n}");
+        update_multi(&multi, &authority, instruction::AddNamed { name })
+    };
+
+    let names: Vec<Instruction> = (0..counter_program::MAX_NAMED_COUNTERS).map(add).collect();
+    for batch in names.chunks(4) {
+        assert_ok(&env.send(batch, &[]).await);
+    }
+    let overflow = add(counter_program::MAX_NAMED_COUNTERS);
+    assert_error(
+        &env.send(&[overflow], &[]).await,
+        CounterError::TooManyCounters,
+    );
+}
+
+#[tokio::test]
+async fn near_capacity_fires_only_inside_the_warn_margin() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let setup = [
+        env.update(&counter, instruction::SetWarnMargin { warn_margin: 100 }),
+        env.update(
+            &counter,
+            instruction::Increment {
+                amount: u64::MAX - 200,
+            },
+        ),
+    ];
+    let result = env.send(&setup, &[]).await;
+    assert_ok(&result);
+    assert!(events::<NearCapacity>(&result).is_empty());
+
+    let ix = env.update(&counter, instruction::Increment { amount: 99 });
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    assert!(events::<NearCapacity>(&result).is_empty());
+
+    let ix = env.update(&counter, instruction::Increment { amount: 1 });
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let warnings = events::<NearCapacity>(&result);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!((warnings[0].counter, warnings[0].remaining), (counter, 100));
+}
+
+#[tokio::test]
+async fn min_increment_rejects_smaller_amounts() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let ix = env.update(&counter, instruction::SetMinIncrement { min_increment: 0 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::InvalidAmount);
+    let ix = env.update(&counter, instruction::SetMinIncrement { min_increment: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let ix = env.update(&counter, instruction::Increment { amount: 4 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::BelowMinimum);
+    let ix = env.update(&counter, instruction::Increment { amount: 5 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.update(&counter, instruction::Increment { amount: 6 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 11);
+}
+
+#[tokio::test]
+async fn handoff_resets_and_transfers_together() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let new_owner = Keypair::new();
+
+    let setup = [
+        env.update(&counter, instruction::Increment { amount: 12 }),
+        env.update(
+            &counter,
+            instruction::SpendCategorized {
+                amount: 3,
+                category: 2,
+            },
+        ),
+        env.update(
+            &counter,
+            instruction::SetMerkleRoot {
+                merkle_root: [7; 32],
+            },
+        ),
+        env.update(
+            &counter,
+            instruction::SetAllowedProgram {
+                allowed_program: Some(Pubkey::new_unique()),
+            },
+        ),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+
+    let intruder = Keypair::new();
+    let intruder_handoff = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::Update {
+            counter,
+            authority: intruder.pubkey(),
+        }
+        .to_account_metas(None),
+        data: instruction::Handoff {
+            new_authority: intruder.pubkey(),
+        }
+        .data(),
+    };
+    let result = env.send(&[intruder_handoff], &[&intruder]).await;
+    assert_error(&result, CounterError::Unauthorized);
+    let unchanged = env.counter(&counter).await;
+    assert_eq!(
+        (unchanged.count, unchanged.authority),
+        (9, env.payer().pubkey())
+    );
+
+    let ix = env.update(
+        &counter,
+        instruction::Handoff {
+            new_authority: new_owner.pubkey(),
+        },
+    );
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let resets = events::<CounterReset>(&result);
+    assert_eq!(resets.len(), 1);
+    assert_eq!((resets[0].counter, resets[0].previous_count), (counter, 9));
+    let changes = events::<AuthorityChanged>(&result);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].counter, counter);
+    assert_eq!(changes[0].old_authority, env.payer().pubkey());
+    assert_eq!(changes[0].new_authority, new_owner.pubkey());
+
+    let handed_off = env.counter(&counter).await;
+    assert_eq!(handed_off.count, 0);
+    assert_eq!(handed_off.authority, new_owner.pubkey());
+    assert_eq!(handed_off.category_totals, [0; SPEND_CATEGORIES]);
+    assert_eq!(handed_off.merkle_root, [0; 32]);
+    assert_eq!(handed_off.allowed_program, None);
+}
+
+#[tokio::test]
+async fn sum_into_never_wraps() {
+    let mut env = TestEnv::new().await;
+    let destination = env.create_counter().await.pubkey();
+    let source = env.create_counter().await.pubkey();
+    let authority = env.payer().pubkey();
+
+    let setup = [
+        env.update(
+            &destination,
+            instruction::SetOverflowMode {
+                overflow_mode: OverflowMode::Wrap,
+            },
+        ),
+        env.update(
+            &destination,
+            instruction::Increment {
+                amount: u64::MAX - 2,
+            },
+        ),
+        env.update(&source, instruction::Increment { amount: 5 }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+
+    let ix = sum_into(&destination, &source, &authority, true);
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Overflow);
+    assert_eq!(env.counter(&destination).await.count, u64::MAX - 2);
+    assert_eq!(env.counter(&source).await.count, 5);
+}
+
+#[tokio::test]
+async fn sum_into_cannot_move_locked_value() {
+    let mut env = TestEnv::new().await;
+    let destination = env.create_counter().await.pubkey();
+    let source = env.create_counter().await.pubkey();
+    let authority = env.payer().pubkey();
+
+    let setup = [
+        env.update(&source, instruction::Increment { amount: 10 }),
+        env.update(&source, instruction::Lock { amount: 4 }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+
+    let ix = sum_into(&destination, &source, &authority, true);
+    let result = env.send(&[ix], &[]).await;
+    assert_error(&result, CounterError::InsufficientCirculating);
+    assert_supply(&env.counter(&source).await, 4, 6);
+    assert_supply(&env.counter(&destination).await, 0, 0);
+
+    // once unlocked, the whole count moves as circulating value
+    let unlock = env.update(&source, instruction::Unlock { amount: 4 });
+    let ix = sum_into(&destination, &source, &authority, true);
+    assert_ok(&env.send(&[unlock, ix], &[]).await);
+    assert_supply(&env.counter(&source).await, 0, 0);
+    assert_supply(&env.counter(&destination).await, 0, 10);
+}
+
+#[tokio::test]
+async fn increment_records_authorities_on_the_leaderboard() {
+    let mut env = TestEnv::new().await;
+    let payer = env.payer().pubkey();
+    let (leaderboard, _) = Pubkey::find_program_address(&[b"leaderboard"], &counter_program::ID);
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::InitializeLeaderboard {
+            leaderboard,
+            payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeLeaderboard {}.data(),
+    };
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let authorities = [Keypair::new(), Keypair::new(), Keypair::new()];
+    let mut counters = Vec::new();
+    for authority in &authorities {
+        let counter = Keypair::new();
+        let setup = [
+            system_instruction::transfer(&payer, &authority.pubkey(), 100_000_000),
+            Instruction {
+                program_id: counter_program::ID,
+                accounts: accounts::Initialize {
+                    counter: counter.pubkey(),
+                    authority: authority.pubkey(),
+                    system_program: system_program::ID,
+                }
+                .to_account_metas(None),
+                data: instruction::Initialize {}.data(),
+            },
+        ];
+        assert_ok(&env.send(&setup, &[authority, &counter]).await);
+        counters.push(counter.pubkey());
+    }
+
+    for (index, amount) in [(0, 4), (1, 9), (2, 6), (0, 7)] {
+        let authority = &authorities[index];
+        let mut accounts = accounts::Update {
+            counter: counters[index],
+            authority: authority.pubkey(),
+        }
+        .to_account_metas(None);
+        accounts.push(AccountMeta::new(leaderboard, false));
+        let ix = Instruction {
+            program_id: counter_program::ID,
+            accounts,
+            data: instruction::Increment { amount }.data(),
+        };
+        assert_ok(&env.send(&[ix], &[authority]).await);
+    }
+
+    let account = env
+        .ctx
+        .banks_client
+        .get_account(leaderboard)
+        .await
+        .unwrap()
+        .unwrap();
+    let board = Leaderboard::try_deserialize(&mut account.data.as_slice()).unwrap();
+    let standings: Vec<(Pubkey, u64)> = board.entries[..board.len as usize]
+        .iter()
+        .map(|entry| (entry.authority, entry.total))
+        .collect();
+    assert_eq!(
+        standings,
+        vec![
+            (authorities[0].pubkey(), 11),
+            (authorities[1].pubkey(), 9),
+            (authorities[2].pubkey(), 6),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn spend_decrements_and_mints_rewards() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let reward = env.setup_reward(&counter).await;
+
+    let ix = env.update(&counter, instruction::Increment { amount: 10 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    let ix = env.with_reward(&counter, &reward, instruction::Spend { amount: 4 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.count, 6);
+    assert_eq!(env.token_balance(&reward.account).await, 4);
+
+    let ix = env.with_reward(&counter, &reward, instruction::Spend { amount: 7 });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::Underflow);
+    assert_eq!(env.counter(&counter).await.count, 6);
+    assert_eq!(env.token_balance(&reward.account).await, 4);
+}
+
+#[tokio::test]
+async fn spend_categorized_tracks_per_category_totals() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let ix = env.update(&counter, instruction::Increment { amount: 20 });
+    assert_ok(&env.send(&[ix], &[]).await);
+
+    let mut spends = Vec::new();
+    for (amount, category) in [(3, 1), (5, 7), (2, 1)] {
+        let ix = env.update(&counter, instruction::SpendCategorized { amount, category });
+        let result = env.send(&[ix], &[]).await;
+        assert_ok(&result);
+        spends.extend(events::<counter_program::Spend>(&result));
+    }
+    let spends: Vec<(Pubkey, u64, u8, Pubkey)> = spends
+        .iter()
+        .map(|spend| (spend.counter, spend.amount, spend.category, spend.authority))
+        .collect();
+    let authority = env.payer().pubkey();
+    assert_eq!(
+        spends,
+        vec![
+            (counter, 3, 1, authority),
+            (counter, 5, 7, authority),
+            (counter, 2, 1, authority),
+        ]
+    );
+
+    let state = env.counter(&counter).await;
+    assert_eq!(state.count, 10);
+    assert_eq!(state.category_totals, [0, 5, 0, 0, 0, 0, 0, 5]);
+
+    let ix = env.update(
+        &counter,
+        instruction::SpendCategorized {
+            amount: 1,
+            category: counter_program::SPEND_CATEGORIES as u8,
+        },
+    );
+    assert_error(&env.send(&[ix], &[]).await, CounterError::InvalidCategory);
+    assert_eq!(env.counter(&counter).await.count, 10);
+}
+
+#[tokio::test]
+async fn apply_decay_is_linear_and_stops_at_locked() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+
+    let setup = [
+        env.update(&counter, instruction::Increment { amount: 100 }),
+        env.update(&counter, instruction::SetDecayPerSlot { decay_per_slot: 2 }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+    let before = env.counter(&counter).await;
+
+    env.ctx.warp_to_slot(before.last_decay_slot + 10).unwrap();
+    let ix = env.update(&counter, instruction::ApplyDecay {});
+    assert_ok(&env.send(&[ix], &[]).await);
+    let after = env.counter(&counter).await;
+    let elapsed = after.last_decay_slot - before.last_decay_slot;
+    assert!(elapsed >= 10);
+    assert_eq!(after.count, 100 - 2 * elapsed);
+
+    let ix = env.update(&counter, instruction::Lock { amount: 30 });
+    assert_ok(&env.send(&[ix], &[]).await);
+    env.ctx.warp_to_slot(after.last_decay_slot + 100).unwrap();
+    let ix = env.update(&counter, instruction::ApplyDecay {});
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_supply(&env.counter(&counter).await, 30, 0);
+}
+
+#[tokio::test]
+async fn list_named_emits_every_named_counter() {
+    let mut env = TestEnv::new().await;
+    let multi = create_multi(&mut env).await;
+    let authority = env.payer().pubkey();
+
+    let setup: Vec<Instruction> = [("alpha", 1), ("beta", 2), ("gamma", 3)]
+        .into_iter()
+        .flat_map(|(name, amount)| {
+            [
+                update_multi(
+                    &multi,
+                    &authority,
+                    instruction::AddNamed { name: name.into() },
+                ),
+                update_multi(
+                    &multi,
+                    &authority,
+                    instruction::IncrementNamed {
+                        name: name.into(),
+                        amount,
+                    },
+                ),
+            ]
+        })
+        .collect();
+    assert_ok(&env.send(&setup, &[]).await);
+
+    let ix = Instruction {
+        program_id: counter_program::ID,
+        accounts: accounts::ReadMulti {
+            multi_counter: multi,
+        }
+        .to_account_metas(None),
+        data: instruction::ListNamed {}.data(),
+    };
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+    let listed = events::<NamedCountersListed>(&result);
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].multi_counter, multi);
+    let named = |name: &str, value| NamedCounter {
+        name: name.into(),
+        value,
+    };
+    assert_eq!(
+        listed[0].counters,
+        vec![named("alpha", 1), named("beta", 2), named("gamma", 3)]
+    );
+}
+
+#[tokio::test]
+async fn handoff_forfeits_pending_rewards() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let reward = env.setup_reward(&counter).await;
+    let new_owner = Keypair::new();
+
+    let setup = [
+        env.update(&counter, instruction::SetRewardPerOp { reward_per_op: 5 }),
+        env.update(&counter, instruction::Increment { amount: 1 }),
+        env.update(&counter, instruction::Increment { amount: 2 }),
+    ];
+    assert_ok(&env.send(&setup, &[]).await);
+    assert_eq!(env.counter(&counter).await.pending_reward, 10);
+
+    let ix = env.update(
+        &counter,
+        instruction::Handoff {
+            new_authority: new_owner.pubkey(),
+        },
+    );
+    assert_ok(&env.send(&[ix], &[]).await);
+    assert_eq!(env.counter(&counter).await.pending_reward, 0);
+
+    let mut claim = env.with_reward(&counter, &reward, instruction::ClaimReward {});
+    claim.accounts[1] = AccountMeta::new_readonly(new_owner.pubkey(), true);
+    let result = env.send(&[claim], &[&new_owner]).await;
+    assert_error(&result, CounterError::NoPendingReward);
+    assert_eq!(env.token_balance(&reward.account).await, 0);
+}
+
+#[tokio::test]
+async fn increment_tagged_emits_tag() {
+    let mut env = TestEnv::new().await;
+    let counter = env.create_counter().await.pubkey();
+    let tag = *b"deposit!";
+
+    let ix = env.update(&counter, instruction::IncrementTagged { amount: 4, tag });
+    let result = env.send(&[ix], &[]).await;
+    assert_ok(&result);
+
+    let tagged = events::<IncrementTagged>(&result);
+    assert_eq!(tagged.len(), 1);
+    assert_eq!(tagged[0].counter, counter);
+    assert_eq!(tagged[0].amount, 4);
+    assert_eq!(tagged[0].tag, tag);
+    assert_eq!(tagged[0].authority, env.payer().pubkey());
+    assert_eq!(env.counter(&counter).await.count, 4);
+
+    let ix = env.update(&counter, instruction::IncrementTagged { amount: 0, tag });
+    assert_error(&env.send(&[ix], &[]).await, CounterError::InvalidAmount);
+}
@@ -0,0 +1,192 @@
+//! `recover_authority` needs the program to look upgradeable, which a program-test
+//! builtin cannot, so these tests call the entrypoint directly with hand-built accounts.
+
+use std::cell::RefCell;
+use std::sync::Once;
+
+use anchor_lang::prelude::{AccountInfo, Pubkey};
+use anchor_lang::solana_program::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+use anchor_lang::{
+    AccountDeserialize, AccountSerialize, AnchorDeserialize, Event, InstructionData, Space,
+};
+use counter_program::{instruction, AuthorityChanged, Counter, CounterError};
+use solana_sdk::account::{AccountSharedData, ReadableAccount};
+use solana_sdk::program_error::ProgramError;
+
+thread_local! {
+    /// `sol_log_data` payloads (and so `emit!` events) logged on this test's thread
+    static LOGGED_DATA: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Default stubs, except that `sol_log_data` is recorded in `LOGGED_DATA`
+struct EventLogStubs;
+
+impl SyscallStubs for EventLogStubs {
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        LOGGED_DATA.with(|logged| logged.borrow_mut().push(fields.concat()));
+    }
+}
+
+/// Events of type `T` emitted since the last `recover` on this thread
+fn events<T: Event>() -> Vec<T> {
+    LOGGED_DATA.with(|logged| {
+        logged
+            .borrow()
+            .iter()
+            .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+            .map(|data| T::deserialize(&mut &data[8..]).unwrap())
+            .collect()
+    })
+}
+
+struct TestAccount {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    is_signer: bool,
+    is_writable: bool,
+    executable: bool,
+}
+
+impl TestAccount {
+    fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key,
+            owner,
+            lamports: 1_000_000_000,
+            data,
+            is_signer: false,
+            is_writable: false,
+            executable: false,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            self.is_signer,
+            self.is_writable,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            self.executable,
+            0,
+        )
+    }
+}
+
+fn loader_state(state: &UpgradeableLoaderState) -> Vec<u8> {
+    let account = AccountSharedData::new_data(1, state, &bpf_loader_upgradeable::ID).unwrap();
+    account.data().to_vec()
+}
+
+/// Run `recover_authority` signed by `signer` on a counter owned by `authority`, for a
+/// program whose upgrade authority is `upgrade_authority`; returns the counter's
+/// address and its state as left by the instruction
+fn recover(
+    authority: Pubkey,
+    upgrade_authority: Pubkey,
+    signer: Pubkey,
+    new_authority: Pubkey,
+) -> Result<(Pubkey, Counter), ProgramError> {
+    static INSTALL_STUBS: Once = Once::new();
+    INSTALL_STUBS.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(EventLogStubs));
+    });
+    LOGGED_DATA.with(|logged| logged.borrow_mut().clear());
+
+    let (program_data, _) =
+        Pubkey::find_program_address(&[counter_program::ID.as_ref()], &bpf_loader_upgradeable::ID);
+
+    let mut counter = Counter::deserialize(&mut &[0u8; Counter::INIT_SPACE][..]).unwrap();
+    counter.authority = authority;
+    let mut counter_data = Vec::new();
+    counter.try_serialize(&mut counter_data).unwrap();
+    let mut counter = TestAccount::new(Pubkey::new_unique(), counter_program::ID, counter_data);
+    counter.is_writable = true;
+
+    let mut program = TestAccount::new(
+        counter_program::ID,
+        bpf_loader_upgradeable::ID,
+        loader_state(&UpgradeableLoaderState::Program {
+            programdata_address: program_data,
+        }),
+    );
+    program.executable = true;
+
+    let mut program_data = TestAccount::new(
+        program_data,
+        bpf_loader_upgradeable::ID,
+        loader_state(&UpgradeableLoaderState::ProgramData {
+            slot: 0,
+            upgrade_authority_address: Some(upgrade_authority),
+        }),
+    );
+
+    let mut signer = TestAccount::new(signer, Pubkey::default(), Vec::new());
+    signer.is_signer = true;
+
+    let infos = [
+        counter.info(),
+        program.info(),
+        program_data.info(),
+        signer.info(),
+    ];
+    let data = instruction::RecoverAuthority { new_authority }.data();
+    counter_program::entry(&counter_program::ID, &infos, &data)?;
+
+    let data = infos[0].data.borrow();
+    Ok((
+        *infos[0].key,
+        Counter::try_deserialize(&mut &data[..]).unwrap(),
+    ))
+}
+
+#[test]
+fn upgrade_authority_can_recover_the_counter() {
+    let authority = Pubkey::new_unique();
+    let upgrade_authority = Pubkey::new_unique();
+    let new_authority = Pubkey::new_unique();
+
+    let (key, counter) = recover(
+        authority,
+        upgrade_authority,
+        upgrade_authority,
+        new_authority,
+    )
+    .unwrap();
+    assert_eq!(counter.authority, new_authority);
+
+    let changes = events::<AuthorityChanged>();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].counter, key);
+    assert_eq!(changes[0].old_authority, authority);
+    assert_eq!(changes[0].new_authority, new_authority);
+}
+
+#[test]
+fn other_signers_cannot_recover_the_counter() {
+    let upgrade_authority = Pubkey::new_unique();
+    let intruder = Pubkey::new_unique();
+
+    let result = recover(Pubkey::new_unique(), upgrade_authority, intruder, intruder);
+    let error = ProgramError::Custom(CounterError::Unauthorized.into());
+    assert!(matches!(result, Err(e) if e == error));
+    assert!(events::<AuthorityChanged>().is_empty());
+}
+
+#[test]
+fn recovery_rejects_the_default_pubkey() {
+    let upgrade_authority = Pubkey::new_unique();
+
+    let result = recover(
+        Pubkey::new_unique(),
+        upgrade_authority,
+        upgrade_authority,
+        Pubkey::default(),
+    );
+    let error = ProgramError::Custom(CounterError::InvalidAuthority.into());
+    assert!(matches!(result, Err(e) if e == error));
+}